@@ -1,45 +1,151 @@
-use anyhow::{bail, Context, Result};
+use crate::config::{ElevenLabsConfig, RetryConfig};
+use crate::generator::{GenProgress, Progress, SoundGenerator, SoundRequest};
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
 use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
 
-const API_URL: &str = "https://api.elevenlabs.io/v1/sound-generation";
-const MODEL_ID: &str = "eleven_text_to_sound_v2";
-const DURATION: f32 = 30.0;
+const SOUND_GENERATION_PATH: &str = "/v1/sound-generation";
 
 #[derive(Serialize)]
-struct SoundGenRequest {
-    text: String,
+struct SoundGenRequest<'a> {
+    text: &'a str,
     duration_seconds: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_influence: Option<f32>,
     #[serde(rename = "loop")]
     loop_audio: bool,
-    model_id: String,
+    model_id: &'a str,
 }
 
+/// A non-success HTTP response from the API.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: String,
+    /// Parsed `Retry-After` header (delay-seconds form only).
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.status {
+            StatusCode::UNAUTHORIZED => {
+                write!(f, "ElevenLabs rejected the API key ({})", self.status)
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                write!(f, "ElevenLabs rate limit hit ({}): {}", self.status, self.body)
+            }
+            _ => write!(f, "ElevenLabs API error {}: {}", self.status, self.body),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
 pub struct ElevenLabsClient {
     client: reqwest::Client,
     api_key: String,
+    url: String,
+    retry: RetryConfig,
 }
 
 impl ElevenLabsClient {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &ElevenLabsConfig) -> Result<Self> {
         let api_key =
             std::env::var("ELEVENLABS_API_KEY").context("ELEVENLABS_API_KEY env var not set")?;
+        Self::with_api_key(config, api_key)
+    }
+
+    pub fn with_api_key(config: &ElevenLabsConfig, api_key: String) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .build()
+            .context("failed to build HTTP client")?;
         Ok(Self {
-            client: reqwest::Client::new(),
+            client,
             api_key,
+            url: format!(
+                "{}{SOUND_GENERATION_PATH}",
+                config.base_url.trim_end_matches('/')
+            ),
+            retry: config.retry.clone(),
         })
     }
 
-    pub async fn generate_sound(&self, prompt: &str) -> Result<Vec<u8>> {
+    /// Generate audio, retrying transient failures with jittered backoff.
+    pub async fn generate_sound(
+        &self,
+        request: &SoundRequest,
+        progress: Progress<'_>,
+    ) -> Result<Vec<u8>> {
+        let max_retries = self.retry.max_retries;
+        let mut retry = 0;
+        loop {
+            let err = match self.request_once(request).await {
+                Ok(audio) => return Ok(audio),
+                Err(e) => e,
+            };
+            if retry >= max_retries {
+                return Err(err);
+            }
+            let Some(delay) = self.retry_delay(&err, retry) else {
+                return Err(err);
+            };
+            retry += 1;
+            progress(GenProgress::Retrying {
+                retry,
+                max_retries,
+                delay,
+            });
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// How long to wait before retrying after `err`, or `None` if it is not
+    /// worth retrying (bad key, bad request, ...).
+    fn retry_delay(&self, err: &anyhow::Error, retries_so_far: u32) -> Option<Duration> {
+        if let Some(api_err) = err.downcast_ref::<ApiError>() {
+            if api_err.status == StatusCode::TOO_MANY_REQUESTS {
+                // Waiting longer than we ever would is the same as failing
+                if let Some(after) = api_err.retry_after {
+                    let max = Duration::from_millis(self.retry.max_delay_ms);
+                    return (after <= max).then_some(after);
+                }
+            } else if !api_err.status.is_server_error() {
+                return None;
+            }
+        } else if err.downcast_ref::<reqwest::Error>().is_none() {
+            return None;
+        }
+
+        // Exponential backoff with "equal jitter": half fixed, half random
+        let exp = self
+            .retry
+            .base_delay_ms
+            .saturating_mul(1 << retries_so_far.min(16));
+        let capped = exp.min(self.retry.max_delay_ms);
+        let jittered = capped / 2 + fastrand::u64(0..=capped / 2);
+        Some(Duration::from_millis(jittered))
+    }
+
+    async fn request_once(&self, request: &SoundRequest) -> Result<Vec<u8>> {
+        let params = &request.params;
         let body = SoundGenRequest {
-            text: prompt.to_string(),
-            duration_seconds: DURATION,
-            loop_audio: true,
-            model_id: MODEL_ID.to_string(),
+            text: &request.prompt,
+            duration_seconds: params.duration_seconds,
+            prompt_influence: params.prompt_influence,
+            loop_audio: params.loop_audio,
+            model_id: &params.model_id,
         };
 
         let resp = self
             .client
-            .post(API_URL)
+            .post(&self.url)
             .header("xi-api-key", &self.api_key)
             .json(&body)
             .send()
@@ -48,11 +154,32 @@ impl ElevenLabsClient {
 
         if !resp.status().is_success() {
             let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            bail!("ElevenLabs API error {status}: {text}");
+            let retry_after = resp
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let body = resp.text().await.unwrap_or_default();
+            return Err(ApiError {
+                status,
+                body,
+                retry_after,
+            }
+            .into());
         }
 
         let bytes = resp.bytes().await.context("failed to read response body")?;
         Ok(bytes.to_vec())
     }
 }
+
+impl SoundGenerator for ElevenLabsClient {
+    fn generate<'a>(
+        &'a self,
+        request: &'a SoundRequest,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(self.generate_sound(request, progress))
+    }
+}
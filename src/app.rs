@@ -1,36 +1,120 @@
 use crate::audio::AudioEngine;
-use crossterm::event::{KeyCode, KeyEvent};
+use crate::control::{AppState, Command, LayerRef, LayerStatus, PendingStatus, RetryView, Status};
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum AppState {
-    Loading { done: usize, total: usize },
-    Playing,
-    Paused,
+/// How long a notice is worth showing.
+pub const NOTICE_DURATION: Duration = Duration::from_secs(4);
+
+/// How long the master bus counts as clipping after it clips.
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+
+/// Longest sleep timer, so that a timer far in the future can't overflow.
+const MAX_SLEEP: Duration = Duration::from_secs(365 * 86400);
+
+/// A layer that is still being read from cache or generated.
+pub struct PendingLayer {
+    pub name: String,
+    pub emoji: String,
+    pub label: String,
+    pub retry: Option<RetryStatus>,
+}
+
+/// Set once the generator has started retrying a layer.
+pub struct RetryStatus {
+    pub retry: u32,
+    pub max_retries: u32,
+    /// When the retry request goes out.
+    pub starts_at: Instant,
+}
+
+/// Fades the master volume out and quits at `ends_at`.
+pub struct SleepTimer {
+    pub ends_at: Instant,
+    /// Length of the fade-out before `ends_at`.
+    pub fade: Duration,
+}
+
+/// Work `apply` hands to the main loop, which owns the generator and the
+/// loader channel.
+pub enum Action {
+    /// Generate a new take for the layer at this index.
+    Reroll(usize),
+    /// Switch the layer at this index to its next cached take.
+    NextTake(usize),
+    /// Load a preset, noise or user preset scene by name as a new layer.
+    AddScene(String),
+    /// Generate a new layer from a free-text prompt.
+    AddPrompt(String),
+    /// Stop the layer at this index and drop it from the mix.
+    RemoveLayer(usize),
+    /// Save the playing layers and volumes as a named mix.
+    SaveMix(String),
 }
 
 pub struct App {
     pub state: AppState,
     pub engine: AudioEngine,
-    pub selected_layer: usize,
+    pub pending: Vec<PendingLayer>,
+    /// Layers (by id) generating a new take, with retry progress if any.
+    pub rerolling: HashMap<u64, Option<RetryStatus>>,
+    /// Layers (by id) switching to another cached take.
+    pub switching: HashSet<u64>,
+    pub actions: Vec<Action>,
     pub should_quit: bool,
     pub errors: Vec<String>,
+    /// Short message for clients to show, and when it was set.
+    pub notice: Option<(String, Instant)>,
+    pub sleep: Option<SleepTimer>,
+    /// When the master bus last clipped.
+    pub clipped_at: Option<Instant>,
 }
 
 impl App {
     pub fn new(engine: AudioEngine, total_layers: usize) -> Self {
-        Self {
-            state: AppState::Loading {
+        let state = if total_layers == 0 {
+            AppState::Playing
+        } else {
+            AppState::Loading {
                 done: 0,
                 total: total_layers,
-            },
+            }
+        };
+        Self {
+            state,
             engine,
-            selected_layer: 0,
+            pending: Vec::new(),
+            rerolling: HashMap::new(),
+            switching: HashSet::new(),
+            actions: Vec::new(),
             should_quit: false,
             errors: Vec::new(),
+            notice: None,
+            sleep: None,
+            clipped_at: None,
+        }
+    }
+
+    pub fn layer_queued(&mut self, name: &str, emoji: &str, label: &str) {
+        self.pending.push(PendingLayer {
+            name: name.to_string(),
+            emoji: emoji.to_string(),
+            label: label.to_string(),
+            retry: None,
+        });
+    }
+
+    pub fn layer_retrying(&mut self, name: &str, status: RetryStatus) {
+        if let Some(p) = self.pending.iter_mut().find(|p| p.name == name) {
+            p.retry = Some(status);
         }
     }
 
-    pub fn layer_loaded(&mut self) {
+    pub fn layer_loaded(&mut self, name: &str) {
+        if let Some(i) = self.pending.iter().position(|p| p.name == name) {
+            self.pending.remove(i);
+        }
         if let AppState::Loading { done, total } = &mut self.state {
             *done += 1;
             if *done >= *total {
@@ -40,72 +124,229 @@ impl App {
     }
 
     pub fn load_error(&mut self, msg: String) {
+        self.notify(msg.clone());
         self.errors.push(msg);
     }
 
-    pub fn on_key(&mut self, key: KeyEvent) {
-        if matches!(self.state, AppState::Loading { .. }) {
-            if key.code == KeyCode::Char('q') {
-                self.should_quit = true;
+    pub fn notify(&mut self, msg: String) {
+        self.notice = Some((msg, Instant::now()));
+    }
+
+    /// Whether a layer with this scene name is playing or loading.
+    pub fn has_layer(&self, name: &str) -> bool {
+        self.engine.layers.iter().any(|l| l.name == name)
+            || self.pending.iter().any(|p| p.name == name)
+    }
+
+    /// Start a sleep timer that ends `after` from now, or cancel it.
+    pub fn set_sleep(&mut self, after: Option<Duration>) -> Result<()> {
+        self.sleep = match after {
+            Some(after) => {
+                let ends_at = Instant::now()
+                    .checked_add(after)
+                    .filter(|_| after <= MAX_SLEEP)
+                    .context("Sleep timer can be at most a year")?;
+                Some(SleepTimer {
+                    ends_at,
+                    fade: self.engine.sleep_window().min(after),
+                })
             }
+            None => None,
+        };
+        if self.sleep.is_none() {
+            self.engine.set_sleep_fade(1.0);
+        }
+        Ok(())
+    }
+
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        self.sleep
+            .as_ref()
+            .map(|s| s.ends_at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Fade the master volume as the sleep timer runs out, and quit when it
+    /// ends. Call once per frame.
+    pub fn tick_sleep(&mut self) {
+        let (Some(timer), Some(remaining)) = (&self.sleep, self.sleep_remaining()) else {
             return;
+        };
+        if remaining.is_zero() {
+            self.should_quit = true;
+        } else if remaining < timer.fade {
+            let factor = remaining.as_secs_f32() / timer.fade.as_secs_f32();
+            self.engine.set_sleep_fade(factor);
         }
+    }
+
+    /// Note clipping on the master bus. Call once per frame.
+    pub fn tick_meter(&mut self) {
+        if self.engine.meter().take_clipped() {
+            self.clipped_at = Some(Instant::now());
+        }
+    }
+
+    /// Index of the layer a command refers to.
+    fn layer_index(&self, layer: &LayerRef) -> Result<usize> {
+        let index = match layer {
+            LayerRef::Position(n) => n.checked_sub(1).filter(|&i| i < self.engine.layers.len()),
+            LayerRef::Name(name) => self.engine.layers.iter().position(|l| &l.name == name),
+            LayerRef::Id(id) => self.engine.layer_index(*id),
+        };
+        index.with_context(|| format!("No layer {} is playing", layer))
+    }
+
+    fn set_playing(&mut self, play: bool) -> Result<()> {
+        if matches!(self.state, AppState::Loading { .. }) {
+            bail!("Still loading");
+        }
+        if play {
+            self.engine.play_all();
+            self.state = AppState::Playing;
+        } else {
+            self.engine.pause_all();
+            self.state = AppState::Paused;
+        }
+        Ok(())
+    }
 
-        match key.code {
-            KeyCode::Char('q') => {
-                self.should_quit = true;
+    /// Carry out a command from the TUI or a control client. Commands that
+    /// load audio or touch the cache are queued in `actions`.
+    pub fn apply(&mut self, command: Command) -> Result<()> {
+        match command {
+            Command::Status => {}
+            Command::Play => self.set_playing(true)?,
+            Command::Pause => self.set_playing(false)?,
+            Command::Toggle => self.set_playing(self.state == AppState::Paused)?,
+            Command::Add { scene } => self.actions.push(Action::AddScene(scene)),
+            Command::AddPrompt { prompt } => self.actions.push(Action::AddPrompt(prompt)),
+            Command::Remove { layer } => {
+                let index = self.layer_index(&layer)?;
+                self.actions.push(Action::RemoveLayer(index));
             }
-            KeyCode::Char(' ') => {
-                self.state = match self.state {
-                    AppState::Playing => {
-                        self.engine.pause_all();
-                        AppState::Paused
-                    }
-                    AppState::Paused => {
-                        self.engine.play_all();
-                        AppState::Playing
-                    }
-                    _ => return,
-                };
+            Command::Volume { layer, volume } => {
+                let index = self.layer_index(&layer)?;
+                self.engine.set_layer_volume(index, volume);
             }
-            KeyCode::Tab => {
-                if !self.engine.layers.is_empty() {
-                    self.selected_layer =
-                        (self.selected_layer + 1) % self.engine.layers.len();
-                }
+            Command::Master { volume } => self.engine.set_master_volume(volume),
+            Command::VolumeBy { layer, step } => {
+                let index = self.layer_index(&layer)?;
+                let volume = self.engine.layers[index].volume + step;
+                self.engine.set_layer_volume(index, volume);
             }
-            KeyCode::BackTab => {
-                if !self.engine.layers.is_empty() {
-                    self.selected_layer = if self.selected_layer == 0 {
-                        self.engine.layers.len() - 1
-                    } else {
-                        self.selected_layer - 1
-                    };
-                }
+            Command::MasterBy { step } => {
+                let volume = self.engine.master_volume + step;
+                self.engine.set_master_volume(volume);
             }
-            KeyCode::Up => {
-                let idx = self.selected_layer;
-                if let Some(layer) = self.engine.layers.get(idx) {
-                    let new_vol = (layer.volume + 0.05).min(1.0);
-                    self.engine.set_layer_volume(idx, new_vol);
-                }
+            Command::Mute { layer } => {
+                let index = self.layer_index(&layer)?;
+                self.engine.toggle_mute(index);
             }
-            KeyCode::Down => {
-                let idx = self.selected_layer;
-                if let Some(layer) = self.engine.layers.get(idx) {
-                    let new_vol = (layer.volume - 0.05).max(0.0);
-                    self.engine.set_layer_volume(idx, new_vol);
-                }
+            Command::Solo { layer } => {
+                let index = self.layer_index(&layer)?;
+                self.engine.toggle_solo(index);
+            }
+            Command::Stereo { layer, pan, width } => {
+                let index = self.layer_index(&layer)?;
+                let mut stereo = self.engine.layers[index].stereo;
+                stereo.pan = pan.unwrap_or(stereo.pan);
+                stereo.width = width.unwrap_or(stereo.width);
+                self.engine.set_stereo(index, stereo);
+            }
+            Command::Eq {
+                layer,
+                low,
+                mid,
+                high,
+            } => {
+                let index = self.layer_index(&layer)?;
+                let mut tone = self.engine.layers[index].tone;
+                tone.low = low.unwrap_or(tone.low);
+                tone.mid = mid.unwrap_or(tone.mid);
+                tone.high = high.unwrap_or(tone.high);
+                self.engine.set_tone(index, tone);
             }
-            KeyCode::Char('+') | KeyCode::Char('=') => {
-                let new_vol = (self.engine.master_volume + 0.05).min(1.0);
-                self.engine.set_master_volume(new_vol);
+            Command::Reroll { layer } => {
+                let index = self.layer_index(&layer)?;
+                self.actions.push(Action::Reroll(index));
             }
-            KeyCode::Char('-') => {
-                let new_vol = (self.engine.master_volume - 0.05).max(0.0);
-                self.engine.set_master_volume(new_vol);
+            Command::NextTake { layer } => {
+                let index = self.layer_index(&layer)?;
+                self.actions.push(Action::NextTake(index));
             }
-            _ => {}
+            Command::SaveMix { name } => self.actions.push(Action::SaveMix(name)),
+            Command::Sleep { secs } => {
+                self.set_sleep(secs.map(Duration::from_secs))?;
+                match secs {
+                    Some(secs) => self.notify(format!("Sleeping in {} min", secs.div_ceil(60))),
+                    None => self.notify("Sleep timer off".to_string()),
+                }
+            }
+            Command::Quit => self.should_quit = true,
+        }
+        Ok(())
+    }
+
+    /// Snapshot of what is playing, with the visualizer's latest `spectrum`.
+    pub fn status(&self, spectrum: &[f64]) -> Status {
+        let retry_view = |r: &RetryStatus| RetryView {
+            retry: r.retry,
+            max_retries: r.max_retries,
+            wait_secs: r
+                .starts_at
+                .saturating_duration_since(Instant::now())
+                .as_secs_f32(),
+        };
+        let layers = self
+            .engine
+            .layers
+            .iter()
+            .map(|layer| {
+                let rerolling = self.rerolling.get(&layer.id);
+                LayerStatus {
+                    id: layer.id,
+                    name: layer.name.clone(),
+                    emoji: layer.emoji.clone(),
+                    label: layer.label.clone(),
+                    volume: layer.volume,
+                    muted: layer.muted,
+                    soloed: layer.soloed,
+                    audible: self.engine.is_audible(layer),
+                    pan: layer.stereo.pan,
+                    width: layer.stereo.width,
+                    tone: layer.tone,
+                    take: layer.clip.as_ref().and_then(|c| c.take),
+                    takes: layer.clip.as_ref().map_or(0, |c| c.takes.len()),
+                    rerolling: rerolling.is_some(),
+                    retry: rerolling.and_then(|r| r.as_ref()).map(retry_view),
+                }
+            })
+            .collect();
+        let pending = self
+            .pending
+            .iter()
+            .map(|p| PendingStatus {
+                name: p.name.clone(),
+                emoji: p.emoji.clone(),
+                label: p.label.clone(),
+                retry: p.retry.as_ref().map(retry_view),
+            })
+            .collect();
+        let notice = self
+            .notice
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < NOTICE_DURATION)
+            .map(|(msg, _)| msg.clone());
+        Status {
+            state: self.state.clone(),
+            master_volume: self.engine.master_volume,
+            layers,
+            pending,
+            sleep_secs: self.sleep_remaining().map(|left| left.as_secs()),
+            limiter_db: self.engine.meter().reduction_db(),
+            clipping: self.clipped_at.is_some_and(|at| at.elapsed() < CLIP_HOLD),
+            spectrum: spectrum.to_vec(),
+            notice,
         }
     }
 }
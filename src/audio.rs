@@ -1,89 +1,406 @@
+use crate::config::AudioConfig;
+use crate::events::{EventSource, EventSpec};
+use crate::generator::SoundRequest;
+use crate::limiter::{Limiter, LimiterMeter, SharedMeter};
+use crate::loudness;
+use crate::noise::{NoiseKind, NoiseSource};
+use crate::ramp::{Gain, RampedSource, SharedGain};
+use crate::stereo::{SharedStereo, Stereo, StereoControl, StereoSource};
 use crate::tap::{self, SharedRing, TappedSource};
+use crate::tone::{SharedTone, Tone, ToneControl, ToneSource};
 use anyhow::{Context, Result};
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+use rodio::dynamic_mixer::{self, DynamicMixerController};
+use rodio::source::Zero;
+use rodio::{Decoder, OutputStream, Sink, Source};
 use std::io::Cursor;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MASTER_VOLUME: f32 = 0.7;
+pub const DEFAULT_LAYER_VOLUME: f32 = 0.8;
+
+/// Format of the master bus every layer is mixed into.
+const BUS_CHANNELS: u16 = 2;
+const BUS_SAMPLE_RATE: u32 = 44100;
+
+/// Where a layer's audio comes from.
+pub enum LayerSource {
+    /// Encoded audio (mp3, wav, ...) from a generator or the cache.
+    Clip(Vec<u8>),
+    /// Built-in procedural noise, synthesized on the fly.
+    Noise(NoiseKind),
+    /// Encoded one-shots played at random intervals.
+    Events {
+        clips: Vec<Vec<u8>>,
+        spec: EventSpec,
+    },
+}
+
+/// The generated clip a layer plays, so it can be re-rolled or switched to
+/// another cached take.
+pub struct ClipTake {
+    pub request: SoundRequest,
+    /// `None` if the clip couldn't be cached.
+    pub take: Option<u32>,
+    /// Every take cached for `request`, ascending.
+    pub takes: Vec<u32>,
+}
 
 #[allow(dead_code)]
 pub struct Layer {
+    /// Stable across other layers coming and going.
+    pub id: u64,
     pub name: String,
     pub emoji: String,
     pub label: String,
+    /// Set volume, kept while muted or soloed out.
     pub volume: f32,
+    pub muted: bool,
+    pub soloed: bool,
+    pub stereo: Stereo,
+    pub tone: Tone,
+    /// Measured loudness of the audio, in LUFS.
+    pub loudness: Option<f32>,
     pub ring: SharedRing,
-    sink: Sink,
+    /// Set for layers playing a generated clip.
+    pub clip: Option<ClipTake>,
+    gain: SharedGain,
+    /// Brings the layer to the target loudness.
+    normalize: f32,
+    placement: SharedStereo,
+    shaping: SharedTone,
+}
+
+/// Fade and ramp durations, from `AudioConfig`.
+struct Fades {
+    volume: Duration,
+    fade_in: Duration,
+    pause: Duration,
+    quit: Duration,
+    sleep: Duration,
 }
 
-impl Layer {
-    fn update_volume(&self, master: f32) {
-        self.sink.set_volume(master * self.volume);
+impl Fades {
+    fn from_config(config: &AudioConfig) -> Self {
+        Self {
+            volume: Duration::from_millis(config.volume_ramp_ms),
+            fade_in: Duration::from_millis(config.fade_in_ms),
+            pause: Duration::from_millis(config.pause_fade_ms),
+            quit: Duration::from_millis(config.quit_fade_ms),
+            sleep: Duration::from_secs(config.sleep_fade_secs),
+        }
     }
 }
 
 pub struct AudioEngine {
     _stream: OutputStream,
-    stream_handle: OutputStreamHandle,
+    /// Plays the master bus: every layer mixed, then limited.
+    _master: Sink,
+    bus: Arc<DynamicMixerController<f32>>,
+    meter: SharedMeter,
     pub layers: Vec<Layer>,
     pub master_volume: f32,
+    next_id: u64,
+    paused: bool,
+    /// Scales the master volume while a sleep timer runs out.
+    sleep_fade: f32,
+    fades: Fades,
+    /// Loudness every layer is normalized to, if enabled.
+    target_lufs: Option<f32>,
+    /// Sources fading out after their layer was removed or its source
+    /// replaced, stopped once the fade is over.
+    retiring: Vec<(SharedGain, Instant)>,
 }
 
 impl AudioEngine {
-    pub fn new() -> Result<Self> {
+    pub fn new(config: &AudioConfig) -> Result<Self> {
         let (stream, stream_handle) =
             OutputStream::try_default().context("failed to open audio output")?;
+        let master = Sink::try_new(&stream_handle).context("failed to create audio sink")?;
+        let (bus, mix) = dynamic_mixer::mixer(BUS_CHANNELS, BUS_SAMPLE_RATE);
+        // The mixer ends when it runs out of sources, so give it one that never does
+        bus.add(Zero::new(BUS_CHANNELS, BUS_SAMPLE_RATE));
+        let meter = LimiterMeter::new();
+        if config.limiter {
+            master.append(Limiter::new(mix, meter.clone()));
+        } else {
+            master.append(mix);
+        }
         Ok(Self {
             _stream: stream,
-            stream_handle,
+            _master: master,
+            bus,
+            meter,
             layers: Vec::new(),
-            master_volume: 0.7,
+            master_volume: DEFAULT_MASTER_VOLUME,
+            next_id: 0,
+            paused: false,
+            sleep_fade: 1.0,
+            fades: Fades::from_config(config),
+            target_lufs: config.normalize_loudness.then_some(config.target_lufs),
+            retiring: Vec::new(),
         })
     }
 
-    pub fn add_layer(&mut self, name: &str, emoji: &str, label: &str, audio_data: Vec<u8>) -> Result<()> {
-        let sink = Sink::try_new(&self.stream_handle).context("failed to create audio sink")?;
-        let cursor = Cursor::new(audio_data);
-        let source = Decoder::new(cursor).context("failed to decode audio")?;
-
+    /// Add a layer that fades in from silence to `volume`. Its audio is
+    /// normalized by its `loudness` in LUFS, when measured.
+    pub fn add_layer(
+        &mut self,
+        name: &str,
+        emoji: &str,
+        label: &str,
+        source: LayerSource,
+        volume: f32,
+        loudness: Option<f32>,
+    ) -> Result<()> {
         let ring = tap::new_ring();
-        let float_source = source.convert_samples::<f32>().repeat_infinite();
-        let tapped = TappedSource::new(float_source, ring.clone());
-        sink.append(tapped);
+        let gain = Gain::new();
+        let placement = StereoControl::new(Stereo::default());
+        let shaping = ToneControl::new(Tone::default());
+        self.start(source, &ring, &gain, &placement, &shaping)?;
 
         let layer = Layer {
+            id: self.next_id,
             name: name.to_string(),
             emoji: emoji.to_string(),
             label: label.to_string(),
-            volume: 0.8,
+            volume: volume.clamp(0.0, 1.0),
+            muted: false,
+            soloed: false,
+            stereo: Stereo::default(),
+            tone: Tone::default(),
+            loudness,
             ring,
-            sink,
+            clip: None,
+            gain,
+            normalize: self.normalization(loudness),
+            placement,
+            shaping,
         };
-        layer.update_volume(self.master_volume);
+        self.next_id += 1;
+        layer.gain.set_paused(self.paused);
+        self.ramp_layer(&layer, self.fades.fade_in);
         self.layers.push(layer);
         Ok(())
     }
 
-    pub fn pause_all(&self) {
+    /// Fade a layer out and take it out of the mix. The caller should
+    /// unregister its ring from the visualizer.
+    pub fn remove_layer(&mut self, index: usize) -> Option<Layer> {
+        if index >= self.layers.len() {
+            return None;
+        }
+        let layer = self.layers.remove(index);
+        layer.gain.ramp_to(0.0, self.fades.volume);
+        self.retire(layer.gain.clone(), self.fades.volume);
+        if layer.soloed {
+            self.apply_volumes();
+        }
+        Some(layer)
+    }
+
+    /// Swap the audio a layer plays, keeping its volume, spectrum tap and
+    /// paused state. The old audio crossfades into the new.
+    pub fn replace_source(
+        &mut self,
+        index: usize,
+        source: LayerSource,
+        loudness: Option<f32>,
+    ) -> Result<()> {
+        let Some(layer) = self.layers.get(index) else {
+            return Ok(());
+        };
+        let gain = Gain::new();
+        gain.set_paused(self.paused);
+        self.start(source, &layer.ring, &gain, &layer.placement, &layer.shaping)?;
+
+        let normalize = self.normalization(loudness);
+        let layer = &mut self.layers[index];
+        layer.gain.ramp_to(0.0, self.fades.volume);
+        let old = std::mem::replace(&mut layer.gain, gain);
+        layer.loudness = loudness;
+        layer.normalize = normalize;
+        self.retire(old, self.fades.volume);
+        let layer = &self.layers[index];
+        self.ramp_layer(layer, self.fades.volume);
+        Ok(())
+    }
+
+    fn start(
+        &self,
+        source: LayerSource,
+        ring: &SharedRing,
+        gain: &SharedGain,
+        placement: &SharedStereo,
+        shaping: &SharedTone,
+    ) -> Result<()> {
+        // EQ, stereo placement, then the spectrum tap, then the gain ramp
+        let play = |source: Box<dyn Source<Item = f32> + Send>| {
+            let shaped = ToneSource::new(source, shaping.clone());
+            let placed = StereoSource::new(shaped, placement.clone());
+            let tapped = TappedSource::new(placed, ring.clone());
+            self.bus.add(RampedSource::new(tapped, gain.clone()));
+        };
+
+        match source {
+            LayerSource::Clip(audio_data) => {
+                let cursor = Cursor::new(audio_data);
+                let source = Decoder::new(cursor).context("failed to decode audio")?;
+                play(Box::new(source.convert_samples::<f32>().repeat_infinite()));
+            }
+            LayerSource::Noise(kind) => play(Box::new(NoiseSource::new(kind))),
+            LayerSource::Events { clips, spec } => {
+                play(Box::new(EventSource::new(&clips, spec)?));
+            }
+        }
+        Ok(())
+    }
+
+    /// Gain that brings audio measured at `loudness` to the target.
+    fn normalization(&self, loudness: Option<f32>) -> f32 {
+        match (self.target_lufs, loudness) {
+            (Some(target), Some(lufs)) => loudness::gain_for(lufs, target),
+            _ => 1.0,
+        }
+    }
+
+    fn retire(&mut self, gain: SharedGain, fade: Duration) {
+        self.retiring.push((gain, Instant::now() + fade));
+    }
+
+    /// Stop sources whose fade-out has finished. Call once per frame.
+    pub fn reap(&mut self) {
+        let now = Instant::now();
+        self.retiring.retain(|(gain, until)| {
+            if *until > now {
+                return true;
+            }
+            gain.stop();
+            false
+        });
+    }
+
+    /// Gain reduction and clipping on the master bus.
+    pub fn meter(&self) -> &LimiterMeter {
+        &self.meter
+    }
+
+    /// How long before a sleep timer ends its fade-out starts.
+    pub fn sleep_window(&self) -> Duration {
+        self.fades.sleep
+    }
+
+    /// Scale the master volume by `factor` (0–1) without changing its
+    /// setting.
+    pub fn set_sleep_fade(&mut self, factor: f32) {
+        let factor = factor.clamp(0.0, 1.0);
+        if factor != self.sleep_fade {
+            self.sleep_fade = factor;
+            self.apply_volumes();
+        }
+    }
+
+    /// Fade every layer to silence ahead of quitting, returning how long to
+    /// wait for the fade to finish.
+    pub fn fade_out(&mut self) -> Duration {
+        for layer in &self.layers {
+            layer.gain.ramp_to(0.0, self.fades.quit);
+        }
+        self.fades.quit
+    }
+
+    pub fn layer_index(&self, id: u64) -> Option<usize> {
+        self.layers.iter().position(|l| l.id == id)
+    }
+
+    /// Fade out, then hold every layer where it is.
+    pub fn pause_all(&mut self) {
+        self.paused = true;
         for layer in &self.layers {
-            layer.sink.pause();
+            layer.gain.set_paused(true);
+            layer.gain.ramp_to(0.0, self.fades.pause);
         }
     }
 
-    pub fn play_all(&self) {
+    pub fn play_all(&mut self) {
+        self.paused = false;
         for layer in &self.layers {
-            layer.sink.play();
+            layer.gain.set_paused(false);
+            self.ramp_layer(layer, self.fades.pause);
         }
     }
 
+    /// NaN or infinite volumes are ignored.
     pub fn set_layer_volume(&mut self, index: usize, volume: f32) {
+        if !volume.is_finite() {
+            return;
+        }
         if let Some(layer) = self.layers.get_mut(index) {
             layer.volume = volume.clamp(0.0, 1.0);
-            layer.update_volume(self.master_volume);
+            let layer = &self.layers[index];
+            self.ramp_layer(layer, self.fades.volume);
         }
     }
 
+    /// Move a layer in the stereo field.
+    pub fn set_stereo(&mut self, index: usize, stereo: Stereo) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.stereo = stereo.clamped();
+            layer.placement.set(layer.stereo);
+        }
+    }
+
+    /// Reshape a layer's tone.
+    pub fn set_tone(&mut self, index: usize, tone: Tone) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.tone = tone.clamped();
+            layer.shaping.set(layer.tone);
+        }
+    }
+
+    /// NaN or infinite volumes are ignored.
     pub fn set_master_volume(&mut self, volume: f32) {
+        if !volume.is_finite() {
+            return;
+        }
         self.master_volume = volume.clamp(0.0, 1.0);
+        self.apply_volumes();
+    }
+
+    pub fn toggle_mute(&mut self, index: usize) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.muted = !layer.muted;
+            self.apply_volumes();
+        }
+    }
+
+    /// Soloing any layer silences every layer that isn't soloed.
+    pub fn toggle_solo(&mut self, index: usize) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.soloed = !layer.soloed;
+            self.apply_volumes();
+        }
+    }
+
+    /// Whether a layer can be heard, given mute and solo state.
+    pub fn is_audible(&self, layer: &Layer) -> bool {
+        !layer.muted && (layer.soloed || !self.layers.iter().any(|l| l.soloed))
+    }
+
+    fn apply_volumes(&self) {
         for layer in &self.layers {
-            layer.update_volume(self.master_volume);
+            self.ramp_layer(layer, self.fades.volume);
         }
     }
+
+    /// Ramp a layer's gain to what its volume, mute and solo state and the
+    /// master volume call for.
+    fn ramp_layer(&self, layer: &Layer, over: Duration) {
+        let target = if self.paused || !self.is_audible(layer) {
+            0.0
+        } else {
+            self.master_volume * self.sleep_fade * layer.volume * layer.normalize
+        };
+        layer.gain.ramp_to(target, over);
+    }
 }
@@ -1,9 +1,88 @@
-use anyhow::{Context, Result};
+use crate::config::CacheConfig;
+use crate::generator::{GenParams, SoundRequest};
+use crate::loudness;
+use anyhow::{bail, Context, Result};
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-const MODEL_ID: &str = "eleven_text_to_sound_v2";
-const DURATION: u32 = 30;
+const MANIFEST_FILE: &str = "manifest.json";
+/// Locked while the manifest is read, changed and saved, so other vibeband
+/// processes (a daemon, `vibeband cache ...`) don't overwrite each other.
+const MANIFEST_LOCK_FILE: &str = "manifest.json.lock";
+const QUARANTINE_DIR: &str = "quarantine";
+/// Takes with their loop seam crossfaded, as `<take key>-<fade>ms.wav`.
+const SEAM_DIR: &str = "seams";
+/// Suffix of in-progress writes; renamed into place once complete.
+const TMP_SUFFIX: &str = ".tmp";
+/// In-progress writes older than this are leftovers, even if the process
+/// that started them is still running.
+const TMP_GRACE: Duration = Duration::from_secs(3600);
+
+/// Serializes manifest read-modify-write cycles between loader tasks.
+/// `MANIFEST_LOCK_FILE` does the same between processes.
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Metadata about one cached clip, keyed by `take_key` in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub prompt: String,
+    pub params: GenParams,
+    /// Unix seconds.
+    pub created_at: u64,
+    /// Unix seconds.
+    pub last_used_at: u64,
+    pub bytes: u64,
+    /// Decoded length of the clip, if it could be decoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_seconds: Option<f32>,
+    /// Integrated loudness in LUFS (EBU R128), if it could be measured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness_lufs: Option<f32>,
+    /// Loudness was measured, even if silence or a bad clip gave no value,
+    /// so it isn't decoded again on every hit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub loudness_measured: bool,
+    /// SHA-256 of the clip, checked on every read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Pins used to be per clip; `load_manifest` moves them to
+    /// `Manifest::pinned`.
+    #[serde(default, skip_serializing)]
+    pinned: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Take chosen for each request key; take 1 if absent.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub selected_takes: BTreeMap<String, u32>,
+    /// Request keys whose takes, including ones generated later, are
+    /// exempt from eviction.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pinned: BTreeSet<String>,
+    #[serde(flatten)]
+    pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl Manifest {
+    /// Whether the take (or bare request key) `key` belongs to a pinned request.
+    pub fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.contains(split_take_key(key).0)
+    }
+}
+
+/// One stored generation of a request.
+pub struct Take {
+    pub number: u32,
+    pub data: Vec<u8>,
+}
 
 pub fn cache_dir() -> Result<PathBuf> {
     let home = dirs::home_dir().context("could not determine home directory")?;
@@ -12,30 +91,605 @@ pub fn cache_dir() -> Result<PathBuf> {
     Ok(dir)
 }
 
-pub fn cache_key(prompt: &str) -> String {
-    let input = format!("{prompt}|{DURATION}|loop=true|{MODEL_ID}");
+/// Key derived from every field of the request that is sent to the generator.
+/// Requests with default influence hash the same as before prompt influence
+/// existed, so older cache entries stay valid.
+pub fn cache_key(request: &SoundRequest) -> String {
+    let params = &request.params;
+    let mut input = format!(
+        "{}|{}|loop={}|{}",
+        request.prompt, params.duration_seconds, params.loop_audio, params.model_id
+    );
+    if let Some(influence) = params.prompt_influence {
+        input.push_str(&format!("|influence={influence}"));
+    }
     let hash = Sha256::digest(input.as_bytes());
     hex::encode(&hash[..8]) // first 16 hex chars
 }
 
-pub fn cache_path(prompt: &str) -> Result<PathBuf> {
-    let dir = cache_dir()?;
-    let key = cache_key(prompt);
-    Ok(dir.join(format!("{key}.mp3")))
+/// Whether `key` can name a cached clip: hex digits, and `-` before a take
+/// number. Anything else, such as a path, is refused before it reaches the
+/// file system.
+pub fn is_cache_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f' | b'-'))
+}
+
+pub fn key_path(key: &str) -> Result<PathBuf> {
+    if !is_cache_key(key) {
+        bail!("'{}' is not a cache key", key);
+    }
+    Ok(cache_dir()?.join(format!("{key}.mp3")))
 }
 
-pub fn read_cache(prompt: &str) -> Result<Option<Vec<u8>>> {
-    let path = cache_path(prompt)?;
-    if path.exists() {
-        let data = std::fs::read(&path)?;
-        Ok(Some(data))
+/// Manifest key and file stem of one take. Take 1 lives under the bare
+/// request key, where clips were stored before takes existed.
+pub fn take_key(key: &str, take: u32) -> String {
+    if take <= 1 {
+        key.to_string()
     } else {
-        Ok(None)
+        format!("{key}-{take}")
+    }
+}
+
+fn seam_dir() -> Result<PathBuf> {
+    let dir = cache_dir()?.join(SEAM_DIR);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn seam_path(key: &str, crossfade_ms: u64) -> Result<PathBuf> {
+    Ok(seam_dir()?.join(format!("{key}-{crossfade_ms}ms.wav")))
+}
+
+/// A take processed by `seam::seamless` with this crossfade length.
+pub fn read_seam(key: &str, crossfade_ms: u64) -> Result<Option<Vec<u8>>> {
+    let path = seam_path(key, crossfade_ms)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(std::fs::read(path)?))
+}
+
+pub fn write_seam(key: &str, crossfade_ms: u64, data: &[u8]) -> Result<()> {
+    write_atomic(&seam_path(key, crossfade_ms)?, data)
+}
+
+/// Processed seams on disk: (take key, path, bytes).
+fn seam_files() -> Result<Vec<(String, PathBuf, u64)>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(seam_dir()?)? {
+        let path = entry?.path();
+        let key = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_suffix("ms.wav"))
+            .and_then(|n| n.rsplit_once('-'))
+            .map(|(key, _)| key.to_string());
+        if let Some(key) = key {
+            let bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            found.push((key, path, bytes));
+        }
+    }
+    Ok(found)
+}
+
+/// Bytes of processed seams per take key.
+pub fn seam_bytes() -> Result<BTreeMap<String, u64>> {
+    let mut sizes = BTreeMap::new();
+    for (key, _, bytes) in seam_files()? {
+        *sizes.entry(key).or_insert(0) += bytes;
     }
+    Ok(sizes)
 }
 
-pub fn write_cache(prompt: &str, data: &[u8]) -> Result<()> {
-    let path = cache_path(prompt)?;
-    std::fs::write(&path, data)?;
+fn remove_seams(key: &str) -> Result<()> {
+    for (seam_key, path, _) in seam_files()? {
+        if seam_key == key {
+            std::fs::remove_file(path)?;
+        }
+    }
     Ok(())
 }
+
+/// Split a manifest key or file stem into request key and take number.
+pub fn split_take_key(key: &str) -> (&str, u32) {
+    match key.rsplit_once('-') {
+        Some((base, n)) => match n.parse() {
+            Ok(take) if take > 1 => (base, take),
+            _ => (key, 1),
+        },
+        None => (key, 1),
+    }
+}
+
+/// Numbers of the takes stored for a request, ascending.
+pub fn takes(request: &SoundRequest) -> Result<Vec<u32>> {
+    takes_for_key(&cache_key(request))
+}
+
+pub fn takes_for_key(key: &str) -> Result<Vec<u32>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(cache_dir()?)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("mp3") {
+            continue;
+        }
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let (base, take) = split_take_key(stem);
+        if base == key {
+            found.push(take);
+        }
+    }
+    found.sort_unstable();
+    Ok(found)
+}
+
+/// The take to play for a request: the one last chosen, if still cached.
+pub fn selected_take(request: &SoundRequest) -> Result<u32> {
+    let key = cache_key(request);
+    Ok(load_manifest()?
+        .selected_takes
+        .get(&key)
+        .copied()
+        .unwrap_or(1))
+}
+
+/// Remember `take` as the one to play for this request from now on.
+pub fn select_take(request: &SoundRequest, take: u32) -> Result<()> {
+    let key = cache_key(request);
+    update_manifest(|m| {
+        if take <= 1 {
+            m.selected_takes.remove(&key);
+        } else {
+            m.selected_takes.insert(key, take);
+        }
+    })
+}
+
+/// Read the selected take of a request, falling back to any other intact
+/// take if it has been evicted or found corrupt.
+pub fn read_cache(request: &SoundRequest) -> Result<Option<Take>> {
+    let selected = selected_take(request)?;
+    let mut order = takes(request)?;
+    order.sort_by_key(|&n| n != selected);
+    for number in order {
+        if let Some(data) = read_take(request, number)? {
+            return Ok(Some(Take { number, data }));
+        }
+    }
+    Ok(None)
+}
+
+/// Read one take, verifying it first. A corrupt take is moved to the
+/// quarantine directory and reported as a miss.
+pub fn read_take(request: &SoundRequest, take: u32) -> Result<Option<Vec<u8>>> {
+    let key = take_key(&cache_key(request), take);
+    let path = key_path(&key)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = std::fs::read(&path)?;
+    let checksum = load_manifest()?
+        .entries
+        .get(&key)
+        .and_then(|e| e.sha256.clone());
+    let intact = match checksum {
+        Some(expected) => expected == sha256_hex(&data),
+        // Cached before checksums existed: make sure it at least decodes
+        None => clip_seconds(&data).is_some_and(|secs| secs > 0.0),
+    };
+    if !intact {
+        quarantine(&key)?;
+        return Ok(None);
+    }
+
+    let _ = touch(&key, request, &data);
+    Ok(Some(data))
+}
+
+/// Measured loudness of one take, in LUFS.
+pub fn take_loudness(request: &SoundRequest, take: u32) -> Option<f32> {
+    let key = take_key(&cache_key(request), take);
+    load_manifest()
+        .ok()?
+        .entries
+        .get(&key)
+        .and_then(|e| e.loudness_lufs)
+}
+
+/// Store a freshly generated clip as a new take and select it, then evict
+/// old clips beyond `limits`. Returns the take number.
+pub fn write_cache(request: &SoundRequest, data: &[u8], limits: &CacheConfig) -> Result<u32> {
+    let base = cache_key(request);
+    // Decode before taking any lock
+    let (audio_seconds, loudness_lufs) = (clip_seconds(data), loudness::integrated_clip(data));
+
+    // Files are the source of truth for take numbers. The manifest lock is
+    // held from picking the number until the manifest records the take, so
+    // re-rolls of the same request in other processes can't pick it too.
+    let lock = lock_manifest()?;
+    let take = takes_for_key(&base)?.last().map_or(1, |n| n + 1);
+    let key = take_key(&base, take);
+    let path = key_path(&key)?;
+    write_atomic(&path, data)?;
+
+    let now = unix_now();
+    let entry = CacheEntry {
+        prompt: request.prompt.clone(),
+        params: request.params.clone(),
+        created_at: now,
+        last_used_at: now,
+        bytes: data.len() as u64,
+        audio_seconds,
+        loudness_lufs,
+        loudness_measured: true,
+        sha256: Some(sha256_hex(data)),
+        pinned: false,
+    };
+    let mut manifest = load_manifest()?;
+    manifest.entries.insert(key.clone(), entry);
+    if take > 1 {
+        manifest.selected_takes.insert(base.clone(), take);
+    } else {
+        manifest.selected_takes.remove(&base);
+    }
+    save_manifest(&manifest)?;
+    drop(lock);
+
+    evict(limits.max_bytes(), limits.max_age(), Some(&key))?;
+    Ok(take)
+}
+
+/// Record a hit on a verified clip. Entries cached before the manifest (or
+/// checksums) existed are adopted here, since the request tells us what
+/// produced them.
+fn touch(key: &str, request: &SoundRequest, data: &[u8]) -> Result<()> {
+    let now = unix_now();
+    // Decoding takes a while, so it happens before the lock is taken
+    let known = load_manifest()?.entries.remove(key);
+    let audio_seconds = match &known {
+        Some(entry) => entry.audio_seconds,
+        None => clip_seconds(data),
+    };
+    // Cached before loudness was measured
+    let measure = known.as_ref().is_none_or(|e| !e.loudness_measured);
+    let loudness_lufs = measure.then(|| loudness::integrated_clip(data)).flatten();
+
+    update_manifest(|m| {
+        let entry = m
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| CacheEntry {
+                prompt: request.prompt.clone(),
+                params: request.params.clone(),
+                created_at: now,
+                last_used_at: now,
+                bytes: data.len() as u64,
+                audio_seconds,
+                loudness_lufs: None,
+                loudness_measured: false,
+                sha256: None,
+                pinned: false,
+            });
+        entry.last_used_at = now;
+        if entry.sha256.is_none() {
+            entry.sha256 = Some(sha256_hex(data));
+        }
+        if measure && !entry.loudness_measured {
+            entry.loudness_lufs = loudness_lufs;
+            entry.loudness_measured = true;
+        }
+    })
+}
+
+/// Move a corrupt clip aside and forget its manifest entry.
+fn quarantine(key: &str) -> Result<()> {
+    let dir = cache_dir()?.join(QUARANTINE_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let dest = dir.join(format!("{key}-{}.mp3", unix_now()));
+    std::fs::rename(key_path(key)?, dest)?;
+    remove_seams(key)?;
+    update_manifest(|m| {
+        m.entries.remove(key);
+    })
+}
+
+/// Write to a temp file in the same directory, then rename it over `path`,
+/// so readers never see a half-written file.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}{TMP_SUFFIX}", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result.with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Delete leftovers of interrupted writes. Writes still in progress in
+/// another process are left alone. Returns how many were removed.
+pub fn remove_temp_files() -> Result<usize> {
+    let _lock = lock_manifest()?;
+    let mut removed = 0;
+    let entries = std::fs::read_dir(cache_dir()?)?.chain(std::fs::read_dir(seam_dir()?)?);
+    for entry in entries {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.ends_with(TMP_SUFFIX)
+            && is_abandoned(&path, name)
+            && std::fs::remove_file(&path).is_ok()
+        {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Whether the temp file `name` from `write_atomic` is past `TMP_GRACE`, or
+/// was written by a process that is no longer running. Processes can only
+/// be checked where `/proc` lists them.
+fn is_abandoned(path: &Path, name: &str) -> bool {
+    let age = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok());
+    if age.is_some_and(|age| age >= TMP_GRACE) {
+        return true;
+    }
+    let pid = name
+        .strip_suffix(TMP_SUFFIX)
+        .and_then(|n| n.rsplit_once('.'))
+        .and_then(|(_, pid)| pid.parse::<u32>().ok());
+    let proc = Path::new("/proc");
+    match pid {
+        Some(pid) if proc.join("self").exists() => !proc.join(pid.to_string()).exists(),
+        _ => false,
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn manifest_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join(MANIFEST_FILE))
+}
+
+/// Load the manifest. An unreadable manifest is quarantined and replaced by
+/// an empty one; clips it described are re-adopted as they are played.
+pub fn load_manifest() -> Result<Manifest> {
+    let path = manifest_path()?;
+    if !path.exists() {
+        return Ok(Manifest::default());
+    }
+    let data = std::fs::read(&path)?;
+    match serde_json::from_slice::<Manifest>(&data) {
+        Ok(mut manifest) => {
+            for (key, entry) in &mut manifest.entries {
+                if std::mem::take(&mut entry.pinned) {
+                    manifest.pinned.insert(split_take_key(key).0.to_string());
+                }
+            }
+            Ok(manifest)
+        }
+        Err(_) => {
+            let dir = cache_dir()?.join(QUARANTINE_DIR);
+            std::fs::create_dir_all(&dir)?;
+            std::fs::rename(&path, dir.join(format!("{MANIFEST_FILE}-{}", unix_now())))?;
+            Ok(Manifest::default())
+        }
+    }
+}
+
+fn save_manifest(manifest: &Manifest) -> Result<()> {
+    let path = manifest_path()?;
+    let data = serde_json::to_string_pretty(manifest)?;
+    write_atomic(&path, data.as_bytes())
+}
+
+/// Exclusive use of the manifest, within this process and across processes,
+/// until dropped.
+struct ManifestLock {
+    _guard: MutexGuard<'static, ()>,
+    /// Holds an advisory lock, released when the file is closed.
+    _file: File,
+}
+
+fn lock_manifest() -> Result<ManifestLock> {
+    let guard = MANIFEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = cache_dir()?.join(MANIFEST_LOCK_FILE);
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("failed to lock {}", path.display()))?;
+    Ok(ManifestLock {
+        _guard: guard,
+        _file: file,
+    })
+}
+
+/// Load, modify and save the manifest while holding the manifest lock.
+pub fn update_manifest<T>(f: impl FnOnce(&mut Manifest) -> T) -> Result<T> {
+    let _lock = lock_manifest()?;
+    let mut manifest = load_manifest()?;
+    let out = f(&mut manifest);
+    save_manifest(&manifest)?;
+    Ok(out)
+}
+
+/// Cached clips on disk (by key) that have no manifest entry — typically
+/// generated before the manifest existed.
+pub fn orphan_blobs(manifest: &Manifest) -> Result<Vec<(String, u64)>> {
+    let mut orphans = Vec::new();
+    for entry in std::fs::read_dir(cache_dir()?)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("mp3") {
+            continue;
+        }
+        let Some(key) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        // Not ours, whatever it is
+        if !is_cache_key(key) {
+            continue;
+        }
+        if !manifest.entries.contains_key(key) {
+            let bytes = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            orphans.push((key.to_string(), bytes));
+        }
+    }
+    orphans.sort();
+    Ok(orphans)
+}
+
+/// Delete a clip and its manifest entry. Returns whether anything existed.
+pub fn remove_entry(key: &str) -> Result<bool> {
+    let _lock = lock_manifest()?;
+    let mut manifest = load_manifest()?;
+    let had_blob = remove_files(key)?;
+    let had_entry = forget(&mut manifest, key);
+    save_manifest(&manifest)?;
+    Ok(had_blob || had_entry)
+}
+
+/// Delete a clip and its processed seams. Returns whether the clip existed.
+fn remove_files(key: &str) -> Result<bool> {
+    let path = key_path(key)?;
+    let had_blob = path.exists();
+    if had_blob {
+        std::fs::remove_file(&path)?;
+    }
+    remove_seams(key)?;
+    Ok(had_blob)
+}
+
+/// Drop a clip's manifest entry, and its selection if it was the selected
+/// take. Returns whether there was an entry.
+fn forget(manifest: &mut Manifest, key: &str) -> bool {
+    let (base, take) = split_take_key(key);
+    if manifest.selected_takes.get(base) == Some(&take) {
+        manifest.selected_takes.remove(base);
+    }
+    manifest.entries.remove(key).is_some()
+}
+
+/// Set or clear the pin on the request `key` (or a take of it) belongs
+/// to. Returns the request key.
+pub fn set_pinned(key: &str, pinned: bool) -> Result<String> {
+    let base = split_take_key(key).0.to_string();
+    update_manifest(|m| {
+        if pinned {
+            m.pinned.insert(base.clone());
+        } else {
+            m.pinned.remove(&base);
+        }
+    })?;
+    Ok(base)
+}
+
+/// Totals for clips removed by `evict`.
+#[derive(Debug, Default)]
+pub struct Evicted {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+/// Remove least-recently-played clips until the cache is at most `max_bytes`,
+/// plus any not played within `max_age`. Pinned clips and `keep` (the clip
+/// just written) are never removed. Clips without metadata count as last
+/// played when they were written. A clip's processed seams count towards
+/// its size and go with it.
+pub fn evict(
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    keep: Option<&str>,
+) -> Result<Evicted> {
+    // Decide and remove in one go, so no other writer slips in between
+    let _lock = lock_manifest()?;
+    let mut manifest = load_manifest()?;
+    let seams = seam_bytes()?;
+    let seam_size = |key: &str| seams.get(key).copied().unwrap_or(0);
+    let mut total: u64 =
+        manifest.entries.values().map(|e| e.bytes).sum::<u64>() + seams.values().sum::<u64>();
+
+    // (last used, key, bytes) for everything that may be evicted
+    let mut candidates: Vec<(u64, String, u64)> = manifest
+        .entries
+        .iter()
+        .filter(|(k, _)| !manifest.is_pinned(k) && Some(k.as_str()) != keep)
+        .map(|(k, e)| (e.last_used_at, k.clone(), e.bytes + seam_size(k)))
+        .collect();
+    for (key, bytes) in orphan_blobs(&manifest)? {
+        total += bytes;
+        let bytes = bytes + seam_size(&key);
+        let modified = std::fs::metadata(key_path(&key)?)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if !manifest.is_pinned(&key) && Some(key.as_str()) != keep {
+            candidates.push((modified, key, bytes));
+        }
+    }
+    candidates.sort();
+
+    let cutoff = max_age.map(|age| unix_now().saturating_sub(age.as_secs()));
+
+    let mut evicted = Evicted::default();
+    let mut result = Ok(());
+    for (last_used, key, bytes) in candidates {
+        let too_big = max_bytes.is_some_and(|max| total > max);
+        let too_old = cutoff.is_some_and(|c| last_used < c);
+        if !too_big && !too_old {
+            // Sorted oldest first: nothing after this is older either
+            break;
+        }
+        // Keep the manifest in step with whatever was removed before a failure
+        if let Err(e) = remove_files(&key) {
+            result = Err(e);
+            break;
+        }
+        forget(&mut manifest, &key);
+        total = total.saturating_sub(bytes);
+        evicted.count += 1;
+        evicted.bytes += bytes;
+    }
+    save_manifest(&manifest)?;
+    result.map(|()| evicted)
+}
+
+/// Decoded length of an encoded clip.
+pub fn clip_seconds(data: &[u8]) -> Option<f32> {
+    let decoder = Decoder::new(Cursor::new(data.to_vec())).ok()?;
+    let channels = decoder.channels().max(1) as f32;
+    let rate = decoder.sample_rate().max(1) as f32;
+    let samples = decoder.count() as f32;
+    Some(samples / channels / rate)
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,247 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Settings read from `~/.vibeband/config.json`, with environment overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub elevenlabs: ElevenLabsConfig,
+    pub cache: CacheConfig,
+    pub audio: AudioConfig,
+    pub control: ControlConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElevenLabsConfig {
+    /// Scheme + host (and optional path prefix) of the API, without `/v1/...`.
+    pub base_url: String,
+    pub model_id: String,
+    /// Whole-request timeout, including downloading the audio.
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub retry: RetryConfig,
+}
+
+impl Default for ElevenLabsConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://api.elevenlabs.io".to_string(),
+            model_id: "eleven_text_to_sound_v2".to_string(),
+            timeout_secs: 120,
+            connect_timeout_secs: 10,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Backoff for network errors, 5xx and 429 responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+/// Limits enforced on `~/.vibeband/cache` after every write. Pinned clips
+/// are never evicted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub max_size_mb: Option<u64>,
+    /// Evict clips that have not been played for this many days.
+    pub max_age_days: Option<u64>,
+}
+
+impl CacheConfig {
+    /// `max_size_mb` in bytes. Sizes too large to count mean no limit.
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_size_mb.and_then(|mb| mb.checked_mul(1024 * 1024))
+    }
+
+    /// `max_age_days` as a duration. Ages too long to count mean no limit.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age_days
+            .and_then(|days| days.checked_mul(86400))
+            .map(Duration::from_secs)
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_size_mb: Some(2048),
+            max_age_days: None,
+        }
+    }
+}
+
+/// Fade and ramp times for playback, in milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Volume, mute and solo changes.
+    pub volume_ramp_ms: u64,
+    /// New layers once their audio has loaded.
+    pub fade_in_ms: u64,
+    pub pause_fade_ms: u64,
+    pub quit_fade_ms: u64,
+    /// Final stretch of a sleep timer over which the master volume fades out.
+    pub sleep_fade_secs: u64,
+    /// Crossfade each clip's tail into its head instead of trusting the
+    /// generator for a perfect loop.
+    pub crossfade_seams: bool,
+    pub seam_crossfade_ms: u64,
+    /// Run the mixed layers through a limiter so that loud mixes don't
+    /// clip.
+    pub limiter: bool,
+    /// Scale every clip to `target_lufs`, so that equal layer volumes sound
+    /// equally loud.
+    pub normalize_loudness: bool,
+    pub target_lufs: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            volume_ramp_ms: 80,
+            fade_in_ms: 1500,
+            pause_fade_ms: 250,
+            quit_fade_ms: 500,
+            sleep_fade_secs: 300,
+            crossfade_seams: false,
+            seam_crossfade_ms: 2000,
+            limiter: true,
+            normalize_loudness: true,
+            target_lufs: -20.0,
+        }
+    }
+}
+
+/// Ways to control vibeband besides the TUI and `vibeband ctl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Show up in desktop media controls over D-Bus (Linux only).
+    pub mpris: bool,
+    pub osc: OscConfig,
+    pub http: HttpConfig,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            mpris: true,
+            osc: OscConfig::default(),
+            http: HttpConfig::default(),
+        }
+    }
+}
+
+/// Open Sound Control over UDP, off unless an address is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscConfig {
+    /// Address to take commands on, e.g. `0.0.0.0:9000`.
+    pub listen: Option<String>,
+    /// Where to send spectrum band levels, e.g. `127.0.0.1:9001`.
+    pub feedback: Option<String>,
+}
+
+/// The web UI and its HTTP API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    /// Only this machine can connect by default; `0.0.0.0:8765` opens it
+    /// to the network, which needs a `token`.
+    pub listen: String,
+    /// Host names the server answers to besides its IP addresses and
+    /// `localhost`, e.g. `office-pi.local`.
+    pub hosts: Vec<String>,
+    /// Require this token on API requests, as `Authorization: Bearer` or a
+    /// `token` query parameter. Required unless listening on loopback.
+    pub token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "127.0.0.1:8765".to_string(),
+            hosts: Vec::new(),
+            token: None,
+        }
+    }
+}
+
+fn config_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("could not determine home directory")?;
+    Ok(home.join(".vibeband").join("config.json"))
+}
+
+/// Load the config file (if any), then apply environment overrides.
+pub fn load() -> Result<Config> {
+    let path = config_path()?;
+    let mut config = if path.exists() {
+        let data = std::fs::read_to_string(&path)?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("failed to parse {}", path.display()))?
+    } else {
+        Config::default()
+    };
+    apply_env(&mut config)?;
+    Ok(config)
+}
+
+fn apply_env(config: &mut Config) -> Result<()> {
+    let api = &mut config.elevenlabs;
+    if let Ok(url) = std::env::var("ELEVENLABS_BASE_URL") {
+        api.base_url = url;
+    }
+    if let Ok(model) = std::env::var("ELEVENLABS_MODEL_ID") {
+        api.model_id = model;
+    }
+    if let Some(secs) = env_u64("ELEVENLABS_TIMEOUT_SECS")? {
+        api.timeout_secs = secs;
+    }
+    if let Some(secs) = env_u64("ELEVENLABS_CONNECT_TIMEOUT_SECS")? {
+        api.connect_timeout_secs = secs;
+    }
+    if let Some(n) = env_u64("ELEVENLABS_MAX_RETRIES")? {
+        api.retry.max_retries = u32::try_from(n)
+            .with_context(|| format!("ELEVENLABS_MAX_RETRIES is too large: {n}"))?;
+    }
+
+    let cache = &mut config.cache;
+    if let Some(mb) = env_u64("VIBEBAND_CACHE_MAX_MB")? {
+        cache.max_size_mb = (mb > 0).then_some(mb);
+    }
+    if let Some(days) = env_u64("VIBEBAND_CACHE_MAX_AGE_DAYS")? {
+        cache.max_age_days = (days > 0).then_some(days);
+    }
+    Ok(())
+}
+
+fn env_u64(var: &str) -> Result<Option<u64>> {
+    match std::env::var(var) {
+        Ok(v) => v
+            .parse()
+            .map(Some)
+            .with_context(|| format!("{var} must be a whole number, got '{v}'")),
+        Err(_) => Ok(None),
+    }
+}
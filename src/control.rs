@@ -0,0 +1,426 @@
+use crate::tone::Tone;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use tokio::sync::{mpsc, oneshot};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum AppState {
+    Loading { done: usize, total: usize },
+    Playing,
+    Paused,
+}
+
+/// A layer picked by position (from 1), scene name, or `{"id": n}` with
+/// the `id` from its status, which keeps naming the same layer while
+/// others come and go.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LayerRef {
+    Position(usize),
+    Name(String),
+    Id(#[serde(with = "by_id")] u64),
+}
+
+impl fmt::Display for LayerRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayerRef::Position(i) => write!(f, "{}", i),
+            LayerRef::Name(name) => write!(f, "'{}'", name),
+            LayerRef::Id(id) => write!(f, "with id {}", id),
+        }
+    }
+}
+
+/// `LayerRef::Id` as `{"id": n}`, apart from the bare numbers of positions.
+mod by_id {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct ById {
+        id: u64,
+    }
+
+    pub fn serialize<S: Serializer>(id: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        ById { id: *id }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        Ok(ById::deserialize(deserializer)?.id)
+    }
+}
+
+/// One request on the control socket, sent as a line of JSON such as
+/// `{"cmd":"volume","layer":"rain","volume":0.3}`.
+///
+/// Layer positions count from 1, in the order `Status::layers` lists them,
+/// here and everywhere else: `vibeband ctl`, OSC addresses and the HTTP API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    Status,
+    Play,
+    Pause,
+    Toggle,
+    /// Add a preset, noise or user preset scene as a layer.
+    Add {
+        scene: String,
+    },
+    /// Generate a new layer from a free-text prompt.
+    AddPrompt {
+        prompt: String,
+    },
+    Remove {
+        layer: LayerRef,
+    },
+    /// Set a layer's volume, 0 to 1.
+    Volume {
+        layer: LayerRef,
+        volume: f32,
+    },
+    /// Set the master volume, 0 to 1.
+    Master {
+        volume: f32,
+    },
+    /// Change a layer's volume by `step`, e.g. -0.05. Unlike `Volume`,
+    /// steps sent in quick succession all count.
+    VolumeBy {
+        layer: LayerRef,
+        step: f32,
+    },
+    /// Change the master volume by `step`.
+    MasterBy {
+        step: f32,
+    },
+    /// Toggle mute.
+    Mute {
+        layer: LayerRef,
+    },
+    /// Toggle solo.
+    Solo {
+        layer: LayerRef,
+    },
+    /// Move a layer in the stereo field; fields left out keep their value.
+    Stereo {
+        layer: LayerRef,
+        pan: Option<f32>,
+        width: Option<f32>,
+    },
+    /// Set EQ gains in dB; bands left out keep their value.
+    Eq {
+        layer: LayerRef,
+        low: Option<f32>,
+        mid: Option<f32>,
+        high: Option<f32>,
+    },
+    Reroll {
+        layer: LayerRef,
+    },
+    NextTake {
+        layer: LayerRef,
+    },
+    SaveMix {
+        name: String,
+    },
+    /// Fade out and stop after this many seconds, or cancel the timer.
+    Sleep {
+        secs: Option<u64>,
+    },
+    /// Fade out and stop playing.
+    Quit,
+}
+
+/// The answer to a `Command`, sent back as one line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reply {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: None,
+        }
+    }
+
+    pub fn error(error: String) -> Self {
+        Self {
+            ok: false,
+            error: Some(error),
+            status: None,
+        }
+    }
+}
+
+/// Everything a client needs to show what is playing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    #[serde(flatten)]
+    pub state: AppState,
+    pub master_volume: f32,
+    pub layers: Vec<LayerStatus>,
+    /// Layers still being read from cache or generated.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<PendingStatus>,
+    /// Seconds left on the sleep timer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sleep_secs: Option<u64>,
+    /// Gain reduction on the master bus, in dB.
+    pub limiter_db: f32,
+    /// The master bus clipped within the last second.
+    pub clipping: bool,
+    /// Spectrum band levels, 0 to 1, low to high.
+    #[serde(default)]
+    pub spectrum: Vec<f64>,
+    /// Recent message worth showing, e.g. a load error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notice: Option<String>,
+}
+
+impl Status {
+    /// Whether a layer with this scene name is playing or loading.
+    pub fn has_layer(&self, name: &str) -> bool {
+        self.layers.iter().any(|l| l.name == name) || self.pending.iter().any(|p| p.name == name)
+    }
+
+    /// A playing layer by position as listed (from 1) or scene name.
+    pub fn layer(&self, arg: &str) -> Option<&LayerStatus> {
+        match arg.parse::<usize>() {
+            Ok(n) => n.checked_sub(1).and_then(|i| self.layers.get(i)),
+            Err(_) => self.layers.iter().find(|l| l.name == arg),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerStatus {
+    pub id: u64,
+    pub name: String,
+    pub emoji: String,
+    pub label: String,
+    pub volume: f32,
+    pub muted: bool,
+    pub soloed: bool,
+    /// Whether it can be heard, given mute and solo state.
+    pub audible: bool,
+    pub pan: f32,
+    pub width: f32,
+    #[serde(default, skip_serializing_if = "Tone::is_flat")]
+    pub tone: Tone,
+    /// Take number and takes cached, for generated clips.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take: Option<u32>,
+    #[serde(default)]
+    pub takes: usize,
+    /// Generating a new take.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rerolling: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryView>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingStatus {
+    pub name: String,
+    pub emoji: String,
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetryView>,
+}
+
+/// Progress of a generation being retried.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryView {
+    pub retry: u32,
+    pub max_retries: u32,
+    /// Seconds until the retry goes out.
+    pub wait_secs: f32,
+}
+
+/// A command from a client, with where to send the reply. Every way of
+/// controlling vibeband sends these down one channel to the player.
+pub type Request = (Command, oneshot::Sender<Reply>);
+
+/// Send a command to the player and wait for its reply.
+pub async fn request(tx: &mpsc::UnboundedSender<Request>, command: Command) -> Result<Reply> {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if tx.send((command, reply_tx)).is_err() {
+        bail!("vibeband stopped");
+    }
+    reply_rx.await.context("vibeband stopped")
+}
+
+pub fn socket_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("could not determine home directory")?;
+    let dir = home.join(".vibeband");
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join("control.sock"))
+}
+
+/// Keeps the control socket open; the socket file is removed when the
+/// server is dropped.
+pub struct Server {
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Start serving the control socket, passing commands on to `tx`. Fails if
+/// another vibeband is already listening; a socket left behind by one that
+/// crashed is replaced.
+#[cfg(unix)]
+pub fn serve(tx: mpsc::UnboundedSender<Request>) -> Result<Server> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    let path = socket_path()?;
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            bail!("vibeband is already running ({})", path.display());
+        }
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("failed to listen on {}", path.display()))?;
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let (read, mut write) = stream.into_split();
+                let mut lines = BufReader::new(read).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let reply = match serde_json::from_str::<Command>(&line) {
+                        Ok(command) => match request(&tx, command).await {
+                            Ok(reply) => reply,
+                            Err(_) => break,
+                        },
+                        Err(e) => Reply::error(format!("bad command: {}", e)),
+                    };
+                    let Ok(mut out) = serde_json::to_string(&reply) else {
+                        break;
+                    };
+                    out.push('\n');
+                    if write.write_all(out.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    Ok(Server { path })
+}
+
+#[cfg(not(unix))]
+pub fn serve(_tx: mpsc::UnboundedSender<Request>) -> Result<Server> {
+    bail!("the control socket needs a Unix system")
+}
+
+/// How long a client waits for vibeband to answer.
+#[cfg(unix)]
+const REPLY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// A connection to a running vibeband.
+#[cfg(unix)]
+pub struct Client {
+    reader: tokio::io::BufReader<tokio::net::unix::OwnedReadHalf>,
+    writer: tokio::net::unix::OwnedWriteHalf,
+    /// A reply never came, so the next line read could be its late answer.
+    lost: bool,
+}
+
+#[cfg(unix)]
+impl Client {
+    pub async fn connect() -> Result<Self> {
+        let path = socket_path()?;
+        let stream = tokio::net::UnixStream::connect(&path)
+            .await
+            .with_context(|| {
+                format!(
+                    "no vibeband is running (nothing listening on {})",
+                    path.display()
+                )
+            })?;
+        let (read, writer) = stream.into_split();
+        Ok(Self {
+            reader: tokio::io::BufReader::new(read),
+            writer,
+            lost: false,
+        })
+    }
+
+    /// Send a command and wait for the reply. A reply with an error is
+    /// returned as one.
+    pub async fn send(&mut self, command: &Command) -> Result<Reply> {
+        if self.lost {
+            bail!("lost connection to vibeband");
+        }
+        match tokio::time::timeout(REPLY_TIMEOUT, self.round_trip(command)).await {
+            Ok(reply) => reply,
+            Err(_) => {
+                self.lost = true;
+                bail!("vibeband did not answer");
+            }
+        }
+    }
+
+    async fn round_trip(&mut self, command: &Command) -> Result<Reply> {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+        let mut line = serde_json::to_string(command)?;
+        line.push('\n');
+        self.writer
+            .write_all(line.as_bytes())
+            .await
+            .context("lost connection to vibeband")?;
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply).await? == 0 {
+            bail!("vibeband stopped");
+        }
+        let reply: Reply = serde_json::from_str(&reply)?;
+        if let Some(error) = reply.error {
+            bail!(error);
+        }
+        Ok(reply)
+    }
+}
+
+/// Without Unix sockets there is never a connection.
+#[cfg(not(unix))]
+pub struct Client(std::convert::Infallible);
+
+#[cfg(not(unix))]
+impl Client {
+    pub async fn connect() -> Result<Self> {
+        bail!("the control socket needs a Unix system")
+    }
+
+    pub async fn send(&mut self, _command: &Command) -> Result<Reply> {
+        match self.0 {}
+    }
+}
+
+impl Client {
+    pub async fn status(&mut self) -> Result<Status> {
+        self.send(&Command::Status)
+            .await?
+            .status
+            .context("vibeband sent no status")
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{bail, Context, Result};
+use rodio::source::UniformSourceIterator;
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_4;
+use std::io::Cursor;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+
+/// Length of one-shots generated for user event presets.
+pub const DEFAULT_EVENT_SECONDS: f32 = 4.0;
+
+/// How an event layer scatters its one-shots.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventSpec {
+    /// Time between the starts of consecutive events.
+    pub min_gap_secs: f32,
+    pub max_gap_secs: f32,
+    /// Each event plays at a random gain in this range.
+    pub min_gain: f32,
+    pub max_gain: f32,
+    /// How far events may be panned from centre: 0 keeps them centred, 1
+    /// allows hard left and right.
+    pub pan: f32,
+    /// Distinct one-shots (cached takes) to pick from.
+    pub variations: u32,
+}
+
+impl Default for EventSpec {
+    fn default() -> Self {
+        Self {
+            min_gap_secs: 15.0,
+            max_gap_secs: 60.0,
+            min_gain: 0.5,
+            max_gain: 1.0,
+            pan: 0.8,
+            variations: 3,
+        }
+    }
+}
+
+impl EventSpec {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.min_gap_secs > 0.0 && self.min_gap_secs <= self.max_gap_secs) {
+            bail!(
+                "event spacing must be positive with min <= max, got {}-{}s",
+                self.min_gap_secs,
+                self.max_gap_secs
+            );
+        }
+        if !(0.0 <= self.min_gain && self.min_gain <= self.max_gain && self.max_gain <= 1.0) {
+            bail!(
+                "event gain must be within 0-1 with min <= max, got {}-{}",
+                self.min_gain,
+                self.max_gain
+            );
+        }
+        if !(0.0..=1.0).contains(&self.pan) {
+            bail!("event pan must be between 0 and 1, got {}", self.pan);
+        }
+        if self.variations == 0 {
+            bail!("an event layer needs at least one variation");
+        }
+        Ok(())
+    }
+
+    /// Short human-readable summary, e.g. `every 15-60s, 3 variations`.
+    pub fn describe(&self) -> String {
+        format!(
+            "every {}-{}s, {} variations",
+            self.min_gap_secs, self.max_gap_secs, self.variations
+        )
+    }
+}
+
+/// One event in flight.
+struct Voice {
+    clip: usize,
+    pos: usize,
+    gains: [f32; CHANNELS as usize],
+}
+
+/// An endless stereo source that plays short clips at random intervals,
+/// each at a random gain and pan position.
+pub struct EventSource {
+    /// Decoded clips, interleaved stereo at `SAMPLE_RATE`.
+    clips: Vec<Vec<f32>>,
+    spec: EventSpec,
+    voices: Vec<Voice>,
+    /// Frames until the next event starts.
+    until_next: u64,
+    chan_idx: usize,
+    rng: fastrand::Rng,
+}
+
+impl EventSource {
+    pub fn new(clips: &[Vec<u8>], spec: EventSpec) -> Result<Self> {
+        if clips.is_empty() {
+            bail!("no clips to play");
+        }
+        let clips = clips
+            .iter()
+            .map(|data| {
+                let decoder =
+                    Decoder::new(Cursor::new(data.clone())).context("failed to decode audio")?;
+                let uniform = UniformSourceIterator::new(
+                    decoder.convert_samples::<f32>(),
+                    CHANNELS,
+                    SAMPLE_RATE,
+                );
+                Ok(uniform.collect())
+            })
+            .collect::<Result<Vec<Vec<f32>>>>()?;
+
+        let mut source = Self {
+            clips,
+            spec,
+            voices: Vec::new(),
+            until_next: 0,
+            chan_idx: 0,
+            rng: fastrand::Rng::new(),
+        };
+        source.until_next = source.next_gap();
+        Ok(source)
+    }
+
+    fn next_gap(&mut self) -> u64 {
+        let secs = lerp(
+            self.spec.min_gap_secs,
+            self.spec.max_gap_secs,
+            self.rng.f32(),
+        );
+        (secs * SAMPLE_RATE as f32) as u64
+    }
+
+    fn trigger(&mut self) {
+        let clip = self.rng.usize(..self.clips.len());
+        let gain = lerp(self.spec.min_gain, self.spec.max_gain, self.rng.f32());
+        // Equal-power pan, -1 (left) to 1 (right)
+        let pan = (self.rng.f32() * 2.0 - 1.0) * self.spec.pan;
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        self.voices.push(Voice {
+            clip,
+            pos: 0,
+            gains: [gain * angle.cos(), gain * angle.sin()],
+        });
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+impl Iterator for EventSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let ch = self.chan_idx;
+        if ch == 0 {
+            if self.until_next == 0 {
+                self.trigger();
+                self.until_next = self.next_gap();
+            }
+            self.until_next = self.until_next.saturating_sub(1);
+            let clips = &self.clips;
+            self.voices.retain(|v| v.pos < clips[v.clip].len());
+        }
+        self.chan_idx = (self.chan_idx + 1) % CHANNELS as usize;
+
+        let mut sample = 0.0;
+        for voice in &mut self.voices {
+            sample += self.clips[voice.clip][voice.pos] * voice.gains[ch];
+            voice.pos += 1;
+        }
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for EventSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,180 @@
+use crate::api::ElevenLabsClient;
+use crate::config::Config;
+use anyhow::{anyhow, bail, Result};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Generation settings sent along with a prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenParams {
+    pub duration_seconds: f32,
+    /// 0.0–1.0; `None` leaves it to the backend's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_influence: Option<f32>,
+    #[serde(rename = "loop")]
+    pub loop_audio: bool,
+    pub model_id: String,
+}
+
+impl GenParams {
+    /// Config defaults with `overrides` applied on top.
+    pub fn resolve(config: &Config, overrides: &GenOverrides) -> Self {
+        Self {
+            duration_seconds: overrides.duration_seconds.unwrap_or(DEFAULT_DURATION),
+            prompt_influence: overrides.prompt_influence,
+            loop_audio: overrides.loop_audio.unwrap_or(true),
+            model_id: overrides
+                .model_id
+                .clone()
+                .unwrap_or_else(|| config.elevenlabs.model_id.clone()),
+        }
+    }
+
+    /// Every setting pinned, so resolving it again gives back these params.
+    pub fn to_overrides(&self) -> GenOverrides {
+        GenOverrides {
+            duration_seconds: Some(self.duration_seconds),
+            prompt_influence: self.prompt_influence,
+            loop_audio: Some(self.loop_audio),
+            model_id: Some(self.model_id.clone()),
+        }
+    }
+}
+
+impl Default for GenParams {
+    fn default() -> Self {
+        Self::resolve(&Config::default(), &GenOverrides::default())
+    }
+}
+
+pub const DEFAULT_DURATION: f32 = 30.0;
+pub const MIN_DURATION: f32 = 0.5;
+pub const MAX_DURATION: f32 = 30.0;
+
+/// Per-scene overrides of the generation settings, as stored in user presets
+/// and set from CLI flags. Unset fields fall back to the defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_influence: Option<f32>,
+    #[serde(rename = "loop", default, skip_serializing_if = "Option::is_none")]
+    pub loop_audio: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_id: Option<String>,
+}
+
+impl GenOverrides {
+    /// Field-wise merge: values set in `self` win over `fallback`.
+    pub fn or(&self, fallback: &GenOverrides) -> GenOverrides {
+        GenOverrides {
+            duration_seconds: self.duration_seconds.or(fallback.duration_seconds),
+            prompt_influence: self.prompt_influence.or(fallback.prompt_influence),
+            loop_audio: self.loop_audio.or(fallback.loop_audio),
+            model_id: self.model_id.clone().or_else(|| fallback.model_id.clone()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == GenOverrides::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(d) = self.duration_seconds {
+            if !(MIN_DURATION..=MAX_DURATION).contains(&d) {
+                bail!("duration must be between {MIN_DURATION} and {MAX_DURATION} seconds, got {d}");
+            }
+        }
+        if let Some(p) = self.prompt_influence {
+            if !(0.0..=1.0).contains(&p) {
+                bail!("prompt influence must be between 0 and 1, got {p}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Short human-readable summary, e.g. `10s, influence 0.6, no loop`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(d) = self.duration_seconds {
+            parts.push(format!("{d}s"));
+        }
+        if let Some(p) = self.prompt_influence {
+            parts.push(format!("influence {p}"));
+        }
+        if self.loop_audio == Some(false) {
+            parts.push("no loop".to_string());
+        }
+        if let Some(m) = &self.model_id {
+            parts.push(m.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+/// A single sound-generation request: prompt + generation settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundRequest {
+    pub prompt: String,
+    pub params: GenParams,
+}
+
+impl SoundRequest {
+    pub fn new(prompt: &str, params: GenParams) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            params,
+        }
+    }
+}
+
+/// Status updates a generator reports while working on a request.
+#[derive(Debug, Clone)]
+pub enum GenProgress {
+    /// A transient failure; retry number `retry` of `max_retries` starts after `delay`.
+    Retrying {
+        retry: u32,
+        max_retries: u32,
+        delay: Duration,
+    },
+}
+
+pub type Progress<'a> = &'a (dyn Fn(GenProgress) + Send + Sync);
+
+/// A backend that turns a prompt into encoded audio (mp3, wav, ...).
+pub trait SoundGenerator: Send + Sync {
+    fn generate<'a>(
+        &'a self,
+        request: &'a SoundRequest,
+        progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Vec<u8>>>;
+}
+
+/// Stand-in used when no backend could be configured. Cached layers still
+/// play; anything that needs generating fails with the original reason.
+struct Unavailable {
+    reason: String,
+}
+
+impl SoundGenerator for Unavailable {
+    fn generate<'a>(
+        &'a self,
+        _request: &'a SoundRequest,
+        _progress: Progress<'a>,
+    ) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move { Err(anyhow!("{}", self.reason)) })
+    }
+}
+
+/// Build the generator backend described by the config.
+pub fn from_config(config: &Config) -> Arc<dyn SoundGenerator> {
+    match ElevenLabsClient::new(&config.elevenlabs) {
+        Ok(client) => Arc::new(client),
+        Err(e) => Arc::new(Unavailable {
+            reason: e.to_string(),
+        }),
+    }
+}
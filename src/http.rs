@@ -0,0 +1,466 @@
+use crate::config::HttpConfig;
+use crate::control::{self, AppState, Command, LayerRef, LayerStatus, Reply, Status};
+use crate::ws;
+use anyhow::{bail, Context, Result};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{AUTHORIZATION, CONNECTION, CONTENT_TYPE, HOST, ORIGIN, UPGRADE};
+use hyper::http::uri::Authority;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, watch};
+
+/// The web UI, a single page driven by the API below.
+const INDEX: &str = include_str!("web/index.html");
+/// Request bodies are small JSON objects.
+const MAX_BODY: usize = 64 * 1024;
+
+type Body = Full<Bytes>;
+type Sender = mpsc::UnboundedSender<control::Request>;
+/// A failed request: status code and message.
+type Failure = (StatusCode, String);
+
+/// The HTTP server, stopped when dropped.
+pub struct Http {
+    addr: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Http {
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for Http {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serve the web UI and API, passing commands on to `tx` and pushing
+/// `updates` to WebSocket clients. `None` unless enabled. Fails when
+/// listening beyond this machine without a token.
+pub async fn serve(
+    config: &HttpConfig,
+    tx: Sender,
+    updates: watch::Receiver<Status>,
+) -> Result<Option<Http>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    let listener = TcpListener::bind(&config.listen)
+        .await
+        .with_context(|| format!("failed to listen on {}", config.listen))?;
+    let addr = listener.local_addr()?;
+    // `add_prompt` spends generation credits, so strangers mustn't get in
+    if !addr.ip().is_loopback() && config.token.is_none() {
+        bail!(
+            "the web UI on {} is open to the network, so it needs a \"token\" in the http config",
+            config.listen
+        );
+    }
+    let access = Arc::new(Access {
+        addr,
+        hosts: config.hosts.clone(),
+        token: config.token.clone(),
+    });
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (tx, updates, access) = (tx.clone(), updates.clone(), access.clone());
+            let service =
+                service_fn(move |req| {
+                    let (tx, updates, access) = (tx.clone(), updates.clone(), access.clone());
+                    async move {
+                        let response = match access.check(&req) {
+                            Ok(()) => route(req, &tx, updates).await,
+                            Err(failure) => Err(failure),
+                        };
+                        Ok::<_, Infallible>(response.unwrap_or_else(|(code, error)| {
+                            json_response(code, &Reply::error(error))
+                        }))
+                    }
+                });
+            tokio::spawn(async move {
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .with_upgrades()
+                    .await;
+            });
+        }
+    });
+    Ok(Some(Http { addr, task }))
+}
+
+/// Who may use the server. A page on any other site can make the browser
+/// send requests here, and a DNS name it controls can be pointed at this
+/// machine, so only our own host names and origins get through.
+struct Access {
+    addr: SocketAddr,
+    hosts: Vec<String>,
+    token: Option<String>,
+}
+
+impl Access {
+    fn check(&self, req: &hyper::Request<Incoming>) -> Result<(), Failure> {
+        let headers = req.headers();
+        let host = headers.get(HOST).and_then(|v| v.to_str().ok());
+        let Some(host) = host.filter(|host| self.allows_host(host)) else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Host '{}' is not allowed", host.unwrap_or("")),
+            ));
+        };
+        // Browsers send Origin with cross-site requests and WebSockets
+        if let Some(origin) = headers.get(ORIGIN) {
+            if origin.to_str().ok() != Some(format!("http://{}", host).as_str()) {
+                return Err((
+                    StatusCode::FORBIDDEN,
+                    "Requests from other sites are not allowed".to_string(),
+                ));
+            }
+        }
+        // Forms can post other types across sites without asking first
+        let json = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|t| t.split(';').next().map(str::trim) == Some("application/json"));
+        if matches!(*req.method(), Method::PUT | Method::POST) && !json {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Content-Type must be application/json".to_string(),
+            ));
+        }
+
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        if !req.uri().path().starts_with("/api/") {
+            return Ok(());
+        }
+        let bearer = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        // Browsers can't set headers on a WebSocket
+        let query = req
+            .uri()
+            .query()
+            .into_iter()
+            .flat_map(|q| q.split('&'))
+            .find_map(|pair| pair.strip_prefix("token="));
+        match bearer.or(query) {
+            Some(given) if same_bytes(given.as_bytes(), token.as_bytes()) => Ok(()),
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                "A valid token is required".to_string(),
+            )),
+        }
+    }
+
+    /// `localhost` and the addresses we listen on, at our port, or one of
+    /// the configured host names.
+    fn allows_host(&self, host: &str) -> bool {
+        let Ok(authority) = host.parse::<Authority>() else {
+            return false;
+        };
+        if authority.port_u16().unwrap_or(80) != self.addr.port() {
+            return false;
+        }
+        let name = authority.host();
+        let listen = self.addr.ip();
+        if self.hosts.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+            return true;
+        }
+        if name.eq_ignore_ascii_case("localhost") {
+            return listen.is_loopback() || listen.is_unspecified();
+        }
+        match name.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) => listen.is_unspecified() || ip == listen,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Compare without stopping at the first difference, so response times
+/// don't give the token away.
+fn same_bytes(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Changes to a layer; fields left out stay as they are.
+#[derive(Deserialize)]
+struct LayerUpdate {
+    volume: Option<f32>,
+    muted: Option<bool>,
+    soloed: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct MasterUpdate {
+    volume: f32,
+}
+
+/// - `GET /`: the web UI
+/// - `GET /api/status`: everything `vibeband ctl status --json` shows
+/// - `GET /api/layers` and `GET /api/layers/<layer>`
+/// - `PUT /api/layers/<layer>` with `volume`, `muted` and `soloed`
+/// - `PUT /api/master` with `volume`
+/// - `PUT /api/state` with `state`: `playing` or `paused`
+/// - `POST /api/command` with any control socket command
+/// - `GET /api/ws`: a WebSocket pushing status changes and band levels
+///
+/// `<layer>` is a position from 1 or a scene name.
+async fn route(
+    req: hyper::Request<Incoming>,
+    tx: &Sender,
+    updates: watch::Receiver<Status>,
+) -> Result<Response<Body>, Failure> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (method, parts.as_slice()) {
+        (Method::GET, [""]) => Ok(Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(INDEX))
+            .unwrap()),
+        (Method::GET, ["api", "ws"]) => websocket(req, updates),
+        (Method::GET, ["api", "status"]) => Ok(json(&status(tx).await?)),
+        (Method::GET, ["api", "layers"]) => Ok(json(&status(tx).await?.layers)),
+        (Method::GET, ["api", "layers", layer]) => {
+            let status = status(tx).await?;
+            Ok(json(find_layer(&status, layer)?))
+        }
+        (Method::PUT, ["api", "layers", layer]) => {
+            let update: LayerUpdate = body(req).await?;
+            let status = status(tx).await?;
+            let found = find_layer(&status, layer)?;
+            let layer = || LayerRef::Name(found.name.clone());
+            if let Some(volume) = update.volume {
+                let volume = volume.clamp(0.0, 1.0);
+                send(
+                    tx,
+                    Command::Volume {
+                        layer: layer(),
+                        volume,
+                    },
+                )
+                .await?;
+            }
+            if update.muted.is_some_and(|muted| muted != found.muted) {
+                send(tx, Command::Mute { layer: layer() }).await?;
+            }
+            if update.soloed.is_some_and(|soloed| soloed != found.soloed) {
+                send(tx, Command::Solo { layer: layer() }).await?;
+            }
+            Ok(json(&Reply::ok()))
+        }
+        (Method::PUT, ["api", "master"]) => {
+            let update: MasterUpdate = body(req).await?;
+            let volume = update.volume.clamp(0.0, 1.0);
+            Ok(json(&send(tx, Command::Master { volume }).await?))
+        }
+        (Method::PUT, ["api", "state"]) => {
+            let command = match body(req).await? {
+                AppState::Playing => Command::Play,
+                AppState::Paused => Command::Pause,
+                AppState::Loading { .. } => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "state must be playing or paused".to_string(),
+                    ))
+                }
+            };
+            Ok(json(&send(tx, command).await?))
+        }
+        (Method::POST, ["api", "command"]) => {
+            let command: Command = body(req).await?;
+            Ok(json(&send(tx, command).await?))
+        }
+        _ => Err((StatusCode::NOT_FOUND, format!("No such page: {}", path))),
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Response<Body> {
+    json_response(StatusCode::OK, value)
+}
+
+fn json_response<T: Serialize + ?Sized>(code: StatusCode, value: &T) -> Response<Body> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::builder()
+        .status(code)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+async fn body<T: serde::de::DeserializeOwned>(req: hyper::Request<Incoming>) -> Result<T, Failure> {
+    let bytes = Limited::new(req.into_body(), MAX_BODY)
+        .collect()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("bad request body: {}", e)))?
+        .to_bytes();
+    serde_json::from_slice(&bytes)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("bad request body: {}", e)))
+}
+
+/// Send a command, turning an error reply into a failed request.
+async fn send(tx: &Sender, command: Command) -> Result<Reply, Failure> {
+    let reply = control::request(tx, command)
+        .await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    match reply.error {
+        Some(error) => Err((StatusCode::BAD_REQUEST, error)),
+        None => Ok(reply),
+    }
+}
+
+async fn status(tx: &Sender) -> Result<Status, Failure> {
+    send(tx, Command::Status).await?.status.ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "vibeband sent no status".to_string(),
+    ))
+}
+
+fn find_layer<'a>(status: &'a Status, layer: &str) -> Result<&'a LayerStatus, Failure> {
+    status.layer(layer).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No layer '{}' is playing", layer),
+        )
+    })
+}
+
+/// What the WebSocket pushes, as JSON text frames.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Push<'a> {
+    /// The status without its meters, sent on connecting and whenever it
+    /// changes.
+    Status(&'a Status),
+    /// Meters, sent every frame.
+    Levels {
+        bands: &'a [f64],
+        limiter_db: f32,
+        clipping: bool,
+    },
+}
+
+fn websocket(
+    req: hyper::Request<Incoming>,
+    updates: watch::Receiver<Status>,
+) -> Result<Response<Body>, Failure> {
+    let headers = req.headers();
+    let upgrade = headers.get(UPGRADE).and_then(|v| v.to_str().ok());
+    let key = headers
+        .get("sec-websocket-key")
+        .and_then(|v| v.to_str().ok());
+    let (Some(key), true) = (
+        key,
+        upgrade.is_some_and(|u| u.eq_ignore_ascii_case("websocket")),
+    ) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "expected a WebSocket upgrade".to_string(),
+        ));
+    };
+    let accept = ws::accept_key(key);
+
+    tokio::spawn(async move {
+        if let Ok(upgraded) = hyper::upgrade::on(req).await {
+            let _ = push(TokioIo::new(upgraded), updates).await;
+        }
+    });
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "upgrade")
+        .header("sec-websocket-accept", accept)
+        .body(Body::default())
+        .unwrap())
+}
+
+/// Push status changes and levels to a WebSocket client until it leaves.
+async fn push<S>(stream: S, mut updates: watch::Receiver<Status>) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (mut read, mut write) = tokio::io::split(stream);
+    // Clients have nothing to say but pings and goodbyes
+    let (frames_tx, mut frames) = mpsc::unbounded_channel();
+    let reader = tokio::spawn(async move {
+        while let Ok(frame) = ws::read_frame(&mut read).await {
+            let close = frame.opcode == ws::CLOSE;
+            if (close || frame.opcode == ws::PING) && frames_tx.send(frame).is_err() {
+                break;
+            }
+            if close {
+                break;
+            }
+        }
+    });
+
+    let result = push_updates(&mut write, &mut updates, &mut frames).await;
+    reader.abort();
+    result
+}
+
+async fn push_updates<W: AsyncWrite + Unpin>(
+    write: &mut W,
+    updates: &mut watch::Receiver<Status>,
+    frames: &mut mpsc::UnboundedReceiver<ws::Frame>,
+) -> Result<()> {
+    let mut sent = None;
+    let status = updates.borrow_and_update().clone();
+    push_status(write, status, &mut sent).await?;
+    loop {
+        tokio::select! {
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    return Ok(());
+                }
+                let status = updates.borrow_and_update().clone();
+                push_status(write, status, &mut sent).await?;
+            }
+            frame = frames.recv() => match frame {
+                Some(frame) if frame.opcode == ws::PING => {
+                    ws::write_frame(write, ws::PONG, &frame.payload).await?;
+                }
+                Some(frame) => return ws::write_frame(write, ws::CLOSE, &frame.payload).await,
+                None => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Send the levels, and the rest of the status if it changed since `sent`.
+async fn push_status<W: AsyncWrite + Unpin>(
+    write: &mut W,
+    mut status: Status,
+    sent: &mut Option<String>,
+) -> Result<()> {
+    // Meters change every frame, so they go out on their own
+    let bands = std::mem::take(&mut status.spectrum);
+    let levels = serde_json::to_string(&Push::Levels {
+        bands: &bands,
+        limiter_db: status.limiter_db,
+        clipping: status.clipping,
+    })?;
+    (status.limiter_db, status.clipping) = (0.0, false);
+    let text = serde_json::to_string(&Push::Status(&status))?;
+    if sent.as_ref() != Some(&text) {
+        ws::write_frame(write, ws::TEXT, text.as_bytes()).await?;
+        *sent = Some(text);
+    }
+    ws::write_frame(write, ws::TEXT, levels.as_bytes()).await
+}
@@ -0,0 +1,199 @@
+use rodio::Source;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How far ahead the limiter sees peaks coming.
+const LOOKAHEAD_SECS: f32 = 0.005;
+/// Time constant for gain recovering after a peak.
+const RELEASE_SECS: f32 = 0.15;
+/// Peak level the limiter holds the output to, about -1 dBFS. The soft
+/// clipper starts bending samples above it.
+pub const CEILING: f32 = 0.89;
+/// Frames between meter updates.
+const METER_BLOCK: usize = 512;
+
+/// What the limiter has been doing, read by the UI.
+pub struct LimiterMeter {
+    /// Lowest gain applied during the last block, 1 when not limiting.
+    gain: AtomicU32,
+    /// Set when the soft clipper caught a sample that would have clipped.
+    clipped: AtomicBool,
+}
+
+pub type SharedMeter = Arc<LimiterMeter>;
+
+impl LimiterMeter {
+    pub fn new() -> SharedMeter {
+        Arc::new(Self {
+            gain: AtomicU32::new(1.0f32.to_bits()),
+            clipped: AtomicBool::new(false),
+        })
+    }
+
+    /// Current gain reduction in dB, 0 or more.
+    pub fn reduction_db(&self) -> f32 {
+        let gain = f32::from_bits(self.gain.load(Ordering::Relaxed));
+        -20.0 * gain.max(f32::EPSILON).log10()
+    }
+
+    /// Whether anything clipped since the last call.
+    pub fn take_clipped(&self) -> bool {
+        self.clipped.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// A stereo Source wrapper for the master bus: a look-ahead peak limiter
+/// followed by a soft clipper.
+///
+/// The input is delayed by `LOOKAHEAD_SECS`, so the gain has already come
+/// down by the time a peak reaches the output, and it is never more than
+/// that peak allows. Both channels share one gain to keep the stereo image
+/// steady. Anything above `CEILING` that gets through anyway is bent
+/// smoothly towards full scale instead of clipping hard.
+pub struct Limiter<S> {
+    inner: S,
+    meter: SharedMeter,
+    lookahead: u64,
+    /// Input frames waiting to be played with the gain each needs, oldest
+    /// first.
+    delay: VecDeque<([f32; 2], f32)>,
+    /// Gains needed by upcoming frames, as `(frame, gain)` with gains
+    /// ascending, so the front is the lowest in the look-ahead window.
+    needed: VecDeque<(u64, f32)>,
+    frames: u64,
+    gain: f32,
+    attack: f32,
+    release: f32,
+    frame: [f32; 2],
+    out_idx: usize,
+    block_len: usize,
+    block_gain: f32,
+}
+
+impl<S> Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    /// Wrap a two-channel source.
+    pub fn new(inner: S, meter: SharedMeter) -> Self {
+        let rate = inner.sample_rate() as f32;
+        let lookahead = (LOOKAHEAD_SECS * rate).max(1.0) as u64;
+        Self {
+            inner,
+            meter,
+            lookahead,
+            delay: vec![([0.0; 2], 1.0); lookahead as usize].into(),
+            needed: VecDeque::new(),
+            frames: 0,
+            gain: 1.0,
+            // Close to fully down within the look-ahead
+            attack: 1.0 - (-5.0 / lookahead as f32).exp(),
+            release: 1.0 - (-1.0 / (RELEASE_SECS * rate)).exp(),
+            frame: [0.0; 2],
+            out_idx: 0,
+            block_len: 0,
+            block_gain: 1.0,
+        }
+    }
+
+    fn next_frame(&mut self) -> Option<()> {
+        let input = [self.inner.next()?, self.inner.next()?];
+        let peak = input[0].abs().max(input[1].abs());
+        let need = if peak > CEILING { CEILING / peak } else { 1.0 };
+
+        while self.needed.back().is_some_and(|&(_, g)| g >= need) {
+            self.needed.pop_back();
+        }
+        self.needed.push_back((self.frames, need));
+        while self
+            .needed
+            .front()
+            .is_some_and(|&(f, _)| f + self.lookahead < self.frames)
+        {
+            self.needed.pop_front();
+        }
+        self.frames += 1;
+
+        let target = self.needed.front().map_or(1.0, |&(_, g)| g);
+        let coef = if target < self.gain {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain += (target - self.gain) * coef;
+
+        self.delay.push_back((input, need));
+        let (delayed, delayed_need) = self.delay.pop_front().unwrap_or(([0.0; 2], 1.0));
+        // The attack only gets close to the target, so make up the rest
+        let gain = self.gain.min(delayed_need);
+        let mut clipped = false;
+        for (out, x) in self.frame.iter_mut().zip(delayed) {
+            let x = x * gain;
+            clipped |= x.abs() > 1.0;
+            *out = soft_clip(x);
+        }
+
+        self.block_gain = self.block_gain.min(gain);
+        if clipped {
+            self.meter.clipped.store(true, Ordering::Relaxed);
+        }
+        self.block_len += 1;
+        if self.block_len == METER_BLOCK {
+            let gain = self.block_gain.to_bits();
+            self.meter.gain.store(gain, Ordering::Relaxed);
+            self.block_len = 0;
+            self.block_gain = 1.0;
+        }
+        Some(())
+    }
+}
+
+/// Pass samples up to `CEILING` through and bend louder ones smoothly
+/// towards full scale.
+fn soft_clip(x: f32) -> f32 {
+    let level = x.abs();
+    if level <= CEILING {
+        return x;
+    }
+    let room = 1.0 - CEILING;
+    (CEILING + room * ((level - CEILING) / room).tanh()).copysign(x)
+}
+
+impl<S> Iterator for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.out_idx == 0 {
+            self.next_frame()?;
+        }
+        let sample = self.frame[self.out_idx];
+        self.out_idx = (self.out_idx + 1) % 2;
+        Some(sample)
+    }
+}
+
+impl<S> Source for Limiter<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
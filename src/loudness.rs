@@ -0,0 +1,143 @@
+use rodio::{Decoder, Source};
+use std::f64::consts::PI;
+use std::io::Cursor;
+
+/// Gating blocks are 400ms long, overlapping by 75%.
+const BLOCK_STEPS: usize = 4;
+const STEP_SECS: f64 = 0.1;
+/// Blocks quieter than this are ignored outright.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+/// Blocks this far below the ungated loudness are ignored.
+const RELATIVE_GATE_LU: f64 = 10.0;
+/// Most a quiet clip is boosted by, so near-silence isn't blown up.
+const MAX_BOOST_DB: f32 = 12.0;
+
+/// Integrated loudness of an encoded clip in LUFS, per EBU R128. `None` if
+/// it can't be decoded, is shorter than one 400ms block or is silent.
+pub fn integrated_clip(data: &[u8]) -> Option<f32> {
+    let decoder = Decoder::new(Cursor::new(data.to_vec())).ok()?;
+    integrated(decoder.convert_samples())
+}
+
+/// Integrated loudness of a finite source in LUFS, per EBU R128.
+pub fn integrated<S>(source: S) -> Option<f32>
+where
+    S: Source<Item = f32>,
+{
+    let channels = source.channels().max(1) as usize;
+    let rate = source.sample_rate() as f64;
+    let step_len = (STEP_SECS * rate) as usize;
+    if step_len == 0 {
+        return None;
+    }
+
+    let mut filters = vec![KWeighting::new(rate); channels];
+    // Mean square per 100ms step, summed over channels (all weighted 1)
+    let mut steps = Vec::new();
+    let (mut power, mut frames) = (0.0, 0);
+    for (i, sample) in source.enumerate() {
+        let ch = i % channels;
+        let y = filters[ch].process(sample as f64);
+        power += y * y;
+        if ch == channels - 1 {
+            frames += 1;
+            if frames == step_len {
+                steps.push(power / step_len as f64);
+                power = 0.0;
+                frames = 0;
+            }
+        }
+    }
+
+    let blocks: Vec<f64> = steps
+        .windows(BLOCK_STEPS)
+        .map(|w| w.iter().sum::<f64>() / BLOCK_STEPS as f64)
+        .collect();
+    let gated = |threshold: f64| {
+        let kept: Vec<f64> = blocks
+            .iter()
+            .copied()
+            .filter(|&z| lufs(z) > threshold)
+            .collect();
+        (!kept.is_empty()).then(|| kept.iter().sum::<f64>() / kept.len() as f64)
+    };
+
+    let ungated = gated(ABSOLUTE_GATE_LUFS)?;
+    let integrated = gated(lufs(ungated) - RELATIVE_GATE_LU)?;
+    Some(lufs(integrated) as f32)
+}
+
+fn lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.max(f64::MIN_POSITIVE).log10()
+}
+
+/// Linear gain that brings a clip measured at `lufs` to `target`, boosting
+/// by at most `MAX_BOOST_DB`.
+pub fn gain_for(lufs: f32, target: f32) -> f32 {
+    let db = (target - lufs).min(MAX_BOOST_DB);
+    10f32.powf(db / 20.0)
+}
+
+/// The BS.1770 K-weighting pre-filter: a high shelf modelling the head,
+/// then a high-pass. Coefficients are derived for any sample rate, as in
+/// libebur128.
+#[derive(Clone)]
+struct KWeighting {
+    shelf: Biquad,
+    highpass: Biquad,
+}
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+        Self { shelf, highpass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.highpass.process(self.shelf.process(x))
+    }
+}
+
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
@@ -2,105 +2,400 @@ mod api;
 mod app;
 mod audio;
 mod cache;
+mod config;
+mod control;
+mod events;
+mod generator;
+mod http;
+mod limiter;
+mod loudness;
+#[cfg(target_os = "linux")]
+mod mpris;
+mod noise;
+mod osc;
+mod parse;
+mod picker;
 mod presets;
+mod ramp;
+mod seam;
+mod stereo;
 mod tap;
+mod tone;
 mod tui;
+mod ui;
 mod user_presets;
 mod visualizer;
+mod ws;
 
 use anyhow::{bail, Result};
-use app::App;
-use audio::AudioEngine;
+use app::{Action, App, RetryStatus};
+use audio::{AudioEngine, ClipTake, LayerSource, DEFAULT_LAYER_VOLUME, DEFAULT_MASTER_VOLUME};
 use clap::Parser;
+use config::{AudioConfig, Config};
+use control::{AppState, Command, LayerRef, LayerStatus, Reply, Status};
 use crossterm::{
     event::{self, Event, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use presets::PRESETS;
+use events::{EventSpec, DEFAULT_EVENT_SECONDS};
+use generator::{GenOverrides, GenParams, GenProgress, Progress, SoundGenerator, SoundRequest};
+use noise::{NoiseKind, NOISE_KINDS};
+use parse::{parse_level, parse_mix_layer, parse_span, parse_span_range};
+use presets::{EVENT_PRESETS, PRESETS};
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use std::io::stdout;
-use std::time::Duration;
-use tokio::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use stereo::Stereo;
+use tokio::sync::{mpsc, watch};
+use tone::Tone;
+use ui::Ui;
+use user_presets::{Mix, MixLayer, MixSource, UserPreset};
 use visualizer::Visualizer;
 
+/// How often the TUI redraws and the player checks on loaders and clients.
+const TICK_RATE: Duration = Duration::from_millis(50);
+
 /// Ambient sound generator for your terminal
 #[derive(Parser)]
 #[command(name = "vibeband", version, about)]
 struct Cli {
-    /// Scene name(s) to play, or a subcommand: save, remove, list.
+    /// Scene name(s) to play, or a subcommand: save, remove, list, ctl.
+    #[arg(allow_negative_numbers = true)]
     scenes: Vec<String>,
 
     /// Custom prompt(s) — generate any sound you describe.
     #[arg(short, long = "custom", value_name = "PROMPT")]
     custom: Vec<String>,
+
+    /// Length of generated clips in seconds (0.5–30).
+    #[arg(short, long, value_name = "SECS")]
+    duration: Option<f32>,
+
+    /// How closely generation follows the prompt (0–1).
+    #[arg(long, value_name = "0-1")]
+    influence: Option<f32>,
+
+    /// Generate one-shot clips instead of seamless loops.
+    #[arg(long)]
+    no_loop: bool,
+
+    /// Generation model id.
+    #[arg(short, long, value_name = "ID")]
+    model: Option<String>,
+
+    /// Fade out and quit after this long, e.g. 45m or 1.5h.
+    #[arg(long, value_name = "TIME", value_parser = parse_span)]
+    sleep: Option<Duration>,
+
+    /// Crossfade loop seams of clips that don't loop cleanly.
+    #[arg(long)]
+    crossfade: bool,
+
+    /// With `save`: make an event preset, played at random intervals of
+    /// MIN-MAX (e.g. 20-90s).
+    #[arg(long, value_name = "MIN-MAX", value_parser = parse_span_range)]
+    every: Option<(Duration, Duration)>,
+
+    /// With `save`: stereo position, -1 (left) to 1 (right).
+    #[arg(long, value_name = "-1..1", allow_negative_numbers = true)]
+    pan: Option<f32>,
+
+    /// With `save`: stereo width, 0 (mono) to 1 (original).
+    #[arg(long, value_name = "0-1")]
+    width: Option<f32>,
+
+    /// With `save`: low shelf EQ gain in dB, -12 to 12.
+    #[arg(long, value_name = "DB", allow_negative_numbers = true)]
+    low: Option<f32>,
+
+    /// With `save`: mid EQ gain in dB, -12 to 12.
+    #[arg(long, value_name = "DB", allow_negative_numbers = true)]
+    mid: Option<f32>,
+
+    /// With `save`: high shelf EQ gain in dB, -12 to 12.
+    #[arg(long, value_name = "DB", allow_negative_numbers = true)]
+    high: Option<f32>,
+
+    /// With `ctl status`: print the full status as JSON.
+    #[arg(long)]
+    json: bool,
+}
+
+impl Cli {
+    /// Generation settings given on the command line.
+    fn gen_overrides(&self) -> Result<GenOverrides> {
+        let overrides = GenOverrides {
+            duration_seconds: self.duration,
+            prompt_influence: self.influence,
+            loop_audio: self.no_loop.then_some(false),
+            model_id: self.model.clone(),
+        };
+        overrides.validate()?;
+        Ok(overrides)
+    }
+
+    /// Stereo placement given on the command line.
+    fn stereo(&self) -> Result<Stereo> {
+        let stereo = Stereo {
+            pan: self.pan.unwrap_or(0.0),
+            width: self.width.unwrap_or(1.0),
+        };
+        if stereo.clamped() != stereo {
+            bail!("pan must be between -1 and 1 and width between 0 and 1");
+        }
+        Ok(stereo)
+    }
+
+    /// EQ given on the command line.
+    fn tone(&self) -> Result<Tone> {
+        let tone = Tone {
+            low: self.low.unwrap_or(0.0),
+            mid: self.mid.unwrap_or(0.0),
+            high: self.high.unwrap_or(0.0),
+        };
+        tone.validate()?;
+        Ok(tone)
+    }
 }
 
 struct Scene {
     name: String,
     emoji: String,
     label: String,
-    prompt: String,
+    source: SceneSource,
+    /// Starting layer volume, when restored from a saved mix.
+    volume: Option<f32>,
+    stereo: Stereo,
+    tone: Tone,
+}
+
+enum SceneSource {
+    Prompt(SoundRequest),
+    Noise(NoiseKind),
+    /// One-shots generated from the request, scattered by `spec`.
+    Events {
+        request: SoundRequest,
+        spec: EventSpec,
+    },
 }
 
 struct LoadedLayer {
     name: String,
     emoji: String,
     label: String,
-    result: Result<Vec<u8>>,
+    volume: Option<f32>,
+    stereo: Stereo,
+    tone: Tone,
+    /// The audio, its take if generated and its loudness in LUFS.
+    result: Result<(LayerSource, Option<ClipTake>, Option<f32>)>,
+}
+
+/// A take ready to play: its audio, which take it is and its loudness in
+/// LUFS.
+type NewTake = (Vec<u8>, ClipTake, Option<f32>);
+
+enum LoadEvent {
+    /// Something worth telling the user that didn't stop a load.
+    Notice(String),
+    Retrying {
+        name: String,
+        status: RetryStatus,
+    },
+    Loaded(LoadedLayer),
+    RerollRetrying {
+        id: u64,
+        status: RetryStatus,
+    },
+    Rerolled {
+        id: u64,
+        result: Result<NewTake>,
+    },
+    /// `None` when there was no other take to switch to.
+    TakeSwitched {
+        id: u64,
+        result: Result<Option<NewTake>>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    let overrides = cli.gen_overrides()?;
 
     // Handle subcommands: save, remove, list
     if let Some(first) = cli.scenes.first() {
+        if first == "mix" && cli.scenes.get(1).is_some_and(|s| s == "save") {
+            return cmd_mix_save(&cli.scenes[2..], &cli.custom, &overrides);
+        }
         match first.as_str() {
-            "save" => return cmd_save(&cli.scenes[1..]),
+            "save" => {
+                let (stereo, tone) = (cli.stereo()?, cli.tone()?);
+                return cmd_save(&cli.scenes[1..], &overrides, cli.every, stereo, tone);
+            }
             "remove" => return cmd_remove(&cli.scenes[1..]),
             "list" => return cmd_list(),
+            "cache" => return cmd_cache(&cli.scenes[1..], &overrides),
+            "ctl" => return cmd_ctl(&cli.scenes[1..], &cli.custom, cli.json).await,
+            "attach" => {
+                let client = control::Client::connect().await?;
+                let mut terminal = enter_terminal()?;
+                let result = run_attached(&mut terminal, client).await;
+                leave_terminal()?;
+                return result;
+            }
             _ => {}
         }
     }
 
-    if cli.scenes.is_empty() && cli.custom.is_empty() {
+    let daemon = cli.scenes.first().is_some_and(|s| s == "daemon");
+    if !daemon && cli.scenes.is_empty() && cli.custom.is_empty() {
         print_help()?;
         return Ok(());
     }
 
+    let mut config = config::load()?;
+    config.audio.crossfade_seams |= cli.crossfade;
+    let names = if daemon {
+        &cli.scenes[1..]
+    } else {
+        &cli.scenes[..]
+    };
+    let (scenes, master_volume) = collect_scenes(names, &cli.custom, &config, &overrides)?;
+
+    // Commands from the control socket and desktop integrations
+    let (requests_tx, requests) = mpsc::unbounded_channel();
+    if daemon {
+        let _server = control::serve(requests_tx.clone())?;
+        #[cfg(target_os = "linux")]
+        let _mpris = match config.control.mpris {
+            true => mpris::serve(requests_tx.clone())
+                .await
+                .inspect_err(|e| eprintln!("MPRIS unavailable: {}", e))
+                .ok(),
+            false => None,
+        };
+        let session = Session::new(
+            &config,
+            &overrides,
+            scenes,
+            master_volume,
+            cli.sleep,
+            requests,
+        )?;
+        let _osc = osc::serve(
+            &config.control.osc,
+            requests_tx.clone(),
+            session.subscribe(),
+        )
+        .await?;
+        let http = http::serve(&config.control.http, requests_tx, session.subscribe()).await?;
+        if let Some(http) = &http {
+            eprintln!("Web UI at http://{}", http.addr());
+        }
+        return cmd_daemon(session).await;
+    }
+    if scenes.is_empty() {
+        bail!("No scenes specified. Run `vibeband` to see available options.");
+    }
+
+    // Another vibeband may already be serving the socket
+    let _server = control::serve(requests_tx.clone()).ok();
+    #[cfg(target_os = "linux")]
+    let _mpris = match config.control.mpris {
+        true => mpris::serve(requests_tx.clone()).await.ok(),
+        false => None,
+    };
+    let mut session = Session::new(
+        &config,
+        &overrides,
+        scenes,
+        master_volume,
+        cli.sleep,
+        requests,
+    )?;
+    let osc = osc::serve(
+        &config.control.osc,
+        requests_tx.clone(),
+        session.subscribe(),
+    )
+    .await;
+    let _osc = osc.unwrap_or_else(|e| {
+        session.app.notify(format!("OSC unavailable: {}", e));
+        None
+    });
+    let http = http::serve(&config.control.http, requests_tx, session.subscribe()).await;
+    let _http = match http {
+        Ok(Some(http)) => {
+            session
+                .app
+                .notify(format!("Web UI at http://{}", http.addr()));
+            Some(http)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            session.app.notify(format!("Web UI unavailable: {}", e));
+            None
+        }
+    };
+
+    let mut terminal = enter_terminal()?;
+    let result = run_app(&mut terminal, session).await;
+    leave_terminal()?;
+
+    result
+}
+
+fn enter_terminal() -> Result<Terminal<CrosstermBackend<std::io::Stdout>>> {
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout());
+    Ok(Terminal::new(backend)?)
+}
+
+fn leave_terminal() -> Result<()> {
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+    Ok(())
+}
+
+/// Scenes for the names and prompts given on the command line, and the
+/// master volume of a saved mix among them.
+fn collect_scenes(
+    names: &[String],
+    prompts: &[String],
+    config: &Config,
+    overrides: &GenOverrides,
+) -> Result<(Vec<Scene>, Option<f32>)> {
     let mut scenes: Vec<Scene> = Vec::new();
+    let mut master_volume = None;
+    let mut customs = 0;
 
     // Collect preset + user preset scenes
-    let scene_names: Vec<&str> = cli
-        .scenes
+    let scene_names: Vec<&str> = names
         .iter()
         .map(|s| s.as_str())
         .filter(|s| *s != "mix")
         .collect();
 
     for name in &scene_names {
-        if let Some(p) = presets::find_preset(name) {
-            scenes.push(Scene {
-                name: p.name.to_string(),
-                emoji: p.emoji.to_string(),
-                label: p.label.to_string(),
-                prompt: p.prompt.to_string(),
-            });
-        } else if let Ok(Some(up)) = user_presets::find(name) {
-            scenes.push(Scene {
-                name: name.to_string(),
-                emoji: up.emoji.clone(),
-                label: up.label.clone(),
-                prompt: up.prompt.clone(),
-            });
+        if let Some(scene) = find_scene(name, config, overrides) {
+            scenes.push(scene);
+        } else if let Some(mix) = user_presets::find_mix(name)? {
+            scenes.extend(mix_scenes(name, &mix, config, overrides, &mut customs)?);
+            master_volume = Some(mix.master_volume);
         } else {
             // Collect all available names
             let mut available: Vec<String> =
                 PRESETS.iter().map(|p| p.name.to_string()).collect();
+            available.extend(EVENT_PRESETS.iter().map(|p| p.name.to_string()));
+            for kind in NOISE_KINDS {
+                available.push(format!("noise:{}", kind.name()));
+            }
             if let Ok(user) = user_presets::load() {
-                for key in user.presets.keys() {
+                for key in user.presets.keys().chain(user.mixes.keys()) {
                     available.push(key.clone());
                 }
             }
@@ -113,43 +408,194 @@ async fn main() -> Result<()> {
     }
 
     // Collect custom prompts
-    for (i, prompt) in cli.custom.iter().enumerate() {
-        let label: String = prompt
-            .split_whitespace()
-            .take(3)
-            .collect::<Vec<&str>>()
-            .join(" ");
-        let label = if label.len() < prompt.len() {
-            format!("{}…", label)
-        } else {
-            label
-        };
+    for prompt in prompts {
+        customs += 1;
+        scenes.push(custom_scene(customs, prompt, config, overrides));
+    }
 
-        scenes.push(Scene {
-            name: format!("custom_{}", i + 1),
-            emoji: "🎵".to_string(),
-            label,
-            prompt: prompt.clone(),
-        });
+    Ok((scenes, master_volume))
+}
+
+/// Resolve a built-in, noise or user preset name into a playable scene.
+/// `overrides` (from CLI flags) win over settings stored with the preset.
+fn find_scene(name: &str, config: &Config, overrides: &GenOverrides) -> Option<Scene> {
+    let request = |prompt: &str, stored: &GenOverrides| {
+        let params = GenParams::resolve(config, &overrides.or(stored));
+        SoundRequest::new(prompt, params)
+    };
+
+    if let Some(kind) = NoiseKind::from_scene(name) {
+        Some(Scene {
+            name: name.to_string(),
+            emoji: kind.emoji().to_string(),
+            label: kind.label().to_string(),
+            source: SceneSource::Noise(kind),
+            volume: None,
+            stereo: Stereo::default(),
+            tone: Tone::default(),
+        })
+    } else if let Some(p) = presets::find_preset(name) {
+        Some(Scene {
+            name: p.name.to_string(),
+            emoji: p.emoji.to_string(),
+            label: p.label.to_string(),
+            source: SceneSource::Prompt(request(p.prompt, &GenOverrides::default())),
+            volume: None,
+            stereo: Stereo::default(),
+            tone: Tone::default(),
+        })
+    } else if let Some(p) = presets::find_event_preset(name) {
+        let stored = GenOverrides {
+            duration_seconds: Some(p.duration_seconds),
+            loop_audio: Some(false),
+            ..GenOverrides::default()
+        };
+        Some(Scene {
+            name: p.name.to_string(),
+            emoji: p.emoji.to_string(),
+            label: p.label.to_string(),
+            source: SceneSource::Events {
+                request: request(p.prompt, &stored),
+                spec: p.spec,
+            },
+            volume: None,
+            stereo: Stereo::default(),
+            tone: Tone::default(),
+        })
+    } else if let Ok(Some(up)) = user_presets::find(name) {
+        let request = request(&up.prompt, &up.params);
+        Some(Scene {
+            name: name.to_string(),
+            emoji: up.emoji.clone(),
+            label: up.label.clone(),
+            source: match up.events {
+                Some(spec) => SceneSource::Events { request, spec },
+                None => SceneSource::Prompt(request),
+            },
+            volume: None,
+            stereo: up.stereo,
+            tone: up.tone,
+        })
+    } else {
+        None
     }
+}
 
-    if scenes.is_empty() {
-        bail!("No scenes specified. Run `vibeband` to see available options.");
+/// Scene for the `n`th free-text prompt.
+fn custom_scene(n: usize, prompt: &str, config: &Config, overrides: &GenOverrides) -> Scene {
+    let params = GenParams::resolve(config, overrides);
+    Scene {
+        name: format!("custom_{}", n),
+        emoji: "🎵".to_string(),
+        label: prompt_label(prompt),
+        source: SceneSource::Prompt(SoundRequest::new(prompt, params)),
+        volume: None,
+        stereo: Stereo::default(),
+        tone: Tone::default(),
     }
+}
 
-    // Setup terminal
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout());
-    let mut terminal = Terminal::new(backend)?;
+/// Scenes for every layer of a saved mix, at their saved volumes. Prompt
+/// layers are numbered on from `customs`.
+fn mix_scenes(
+    mix_name: &str,
+    mix: &Mix,
+    config: &Config,
+    overrides: &GenOverrides,
+    customs: &mut usize,
+) -> Result<Vec<Scene>> {
+    let mut scenes = Vec::new();
+    for layer in &mix.layers {
+        let overrides = overrides.or(&layer.params);
+        let mut scene = match &layer.source {
+            MixSource::Scene(name) => match find_scene(name, config, &overrides) {
+                Some(scene) => scene,
+                None => bail!("Mix '{}' uses unknown scene '{}'.", mix_name, name),
+            },
+            MixSource::Prompt(prompt) => {
+                *customs += 1;
+                custom_scene(*customs, prompt, config, &overrides)
+            }
+        };
+        scene.volume = Some(layer.volume);
+        scene.stereo = layer.stereo;
+        scene.tone = layer.tone;
+        scenes.push(scene);
+    }
+    Ok(scenes)
+}
 
-    let result = run_app(&mut terminal, scenes).await;
+/// Record what is playing as a mix. Layers that aren't presets are saved as
+/// prompts with their exact generation settings.
+fn snapshot_mix(app: &App, config: &Config, overrides: &GenOverrides) -> Mix {
+    let layers = app
+        .engine
+        .layers
+        .iter()
+        .map(|layer| {
+            let (source, params) = match &layer.clip {
+                None => (
+                    MixSource::Scene(layer.name.clone()),
+                    GenOverrides::default(),
+                ),
+                Some(_) if find_scene(&layer.name, config, overrides).is_some() => {
+                    (MixSource::Scene(layer.name.clone()), overrides.clone())
+                }
+                Some(clip) => (
+                    MixSource::Prompt(clip.request.prompt.clone()),
+                    clip.request.params.to_overrides(),
+                ),
+            };
+            MixLayer {
+                source,
+                volume: layer.volume,
+                stereo: layer.stereo,
+                tone: layer.tone,
+                params,
+            }
+        })
+        .collect();
+    Mix {
+        master_volume: app.engine.master_volume,
+        layers,
+    }
+}
 
-    // Restore terminal
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
+/// Mixes share a namespace with presets, since both are played by name.
+fn check_mix_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("Mix names can't be empty or contain spaces.");
+    }
+    if presets::find_preset(name).is_some()
+        || presets::find_event_preset(name).is_some()
+        || NoiseKind::from_scene(name).is_some()
+    {
+        bail!("'{}' is a built-in scene. Choose a different name.", name);
+    }
+    if name == user_presets::MIXES_KEY || name == "save" {
+        bail!("'{}' is reserved. Choose a different name.", name);
+    }
+    if user_presets::find(name)?.is_some() {
+        bail!(
+            "'{}' is already a custom preset. Choose a different name.",
+            name
+        );
+    }
+    Ok(())
+}
 
-    result
+/// Label from the first 3 words of a prompt.
+fn prompt_label(prompt: &str) -> String {
+    let label: String = prompt
+        .split_whitespace()
+        .take(3)
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if label.len() < prompt.len() {
+        format!("{}…", label)
+    } else {
+        label
+    }
 }
 
 fn print_help() -> Result<()> {
@@ -158,16 +604,61 @@ fn print_help() -> Result<()> {
     println!("  vibeband <scene>                        Play a preset scene");
     println!("  vibeband mix <scene> <scene>             Mix multiple scenes");
     println!("  vibeband -c \"your prompt here\"           Play a custom sound");
-    println!("  vibeband cafe -c \"vinyl crackle\"         Mix preset + custom\n");
+    println!("  vibeband cafe -c \"vinyl crackle\"         Mix preset + custom");
+    println!("  vibeband noise:brown                     Play offline noise (no API key)");
+    println!("  vibeband rain --sleep 45m                Fade out and quit after 45 minutes");
+    println!("  vibeband rain --crossfade                Crossfade the loop seam of each clip\n");
+    println!("Background playback:");
+    println!("  vibeband daemon rain noise:brown         Play without a terminal");
+    println!("  vibeband attach                          Open the TUI on a running daemon (q detaches)\n");
+    println!("Remote control (of the TUI or a daemon):");
+    println!("  vibeband ctl pause|play|toggle           Pause or resume");
+    println!(
+        "  vibeband ctl vol +10                     Master volume up 10% (-10 down, 60 to set)"
+    );
+    println!("  vibeband ctl layer rain 30               Set a layer's volume by name or position");
+    println!("  vibeband ctl add fire                    Add a scene (or -c \"prompt\")");
+    println!("  vibeband ctl remove|mute|solo rain       Change a layer");
+    println!("  vibeband ctl status [--json]             Show what's playing\n");
     println!("Manage custom presets:");
     println!("  vibeband save <name> \"<prompt>\"          Save/update a preset");
+    println!("  vibeband save <name> \"<prompt>\" --every 20-90s");
+    println!("                                           Save a preset played at random intervals");
+    println!("  vibeband save <name> \"<prompt>\" --low -6 --high -3");
+    println!("                                           Save a preset with EQ cuts/boosts in dB");
     println!("  vibeband remove <name>                   Remove a custom preset");
-    println!("  vibeband list                            List all presets\n");
+    println!("  vibeband list                            List all presets and mixes\n");
+    println!("Saved mixes:");
+    println!(
+        "  vibeband mix save <name> cafe@40 rain    Save scenes (and -c prompts) with volumes"
+    );
+    println!("  vibeband <mix>                           Play a saved mix");
+    println!("  vibeband remove <mix>                    Remove a saved mix\n");
+    println!("Manage the audio cache:");
+    println!("  vibeband cache list                      List cached clips");
+    println!("  vibeband cache show <key|scene>          Show details of a cached clip");
+    println!("  vibeband cache rm <key|scene>...         Delete cached clips");
+    println!("  vibeband cache prune [age]               Enforce cache limits, or drop clips unused for e.g. 30d");
+    println!(
+        "  vibeband cache pin <key|scene>...        Protect clips from eviction (unpin to undo)"
+    );
+    println!("  vibeband cache stats                     Show cache size and totals\n");
     println!("Built-in presets:");
     for p in PRESETS {
         println!("  {} {:<12} {}", p.emoji, p.name, p.label);
     }
 
+    println!("\nOffline noise:");
+    for kind in NOISE_KINDS {
+        let name = format!("noise:{}", kind.name());
+        println!("  {} {:<12} {}", kind.emoji(), name, kind.label());
+    }
+
+    println!("\nRandom events:");
+    for p in EVENT_PRESETS {
+        println!("  {} {:<12} {}", p.emoji, p.name, p.label);
+    }
+
     // Show user presets
     if let Ok(user) = user_presets::load() {
         if !user.presets.is_empty() {
@@ -176,6 +667,12 @@ fn print_help() -> Result<()> {
                 println!("  {} {:<12} {}", p.emoji, name, p.label);
             }
         }
+        if !user.mixes.is_empty() {
+            println!("\nYour mixes:");
+            for (name, mix) in &user.mixes {
+                println!("  🎚 {:<12} {}", name, describe_mix(mix));
+            }
+        }
     }
 
     println!("\nExamples:");
@@ -185,42 +682,90 @@ fn print_help() -> Result<()> {
     Ok(())
 }
 
-fn cmd_save(args: &[String]) -> Result<()> {
+fn cmd_save(
+    args: &[String],
+    params: &GenOverrides,
+    every: Option<(Duration, Duration)>,
+    stereo: Stereo,
+    tone: Tone,
+) -> Result<()> {
     if args.len() < 2 {
-        bail!("Usage: vibeband save <name> \"<prompt>\"\n\nExample:\n  vibeband save myguitar \"soft acoustic guitar fingerpicking with reverb\"");
+        bail!("Usage: vibeband save <name> \"<prompt>\" [--duration SECS] [--influence 0-1] [--no-loop] [--model ID] [--every MIN-MAX] [--pan -1..1] [--width 0-1] [--low DB] [--mid DB] [--high DB]\n\nExample:\n  vibeband save myguitar \"soft acoustic guitar fingerpicking with reverb\"\n  vibeband save creak \"old wooden floorboard creaking once\" --every 30-120s");
     }
 
     let name = &args[0];
     let prompt = args[1..].join(" ");
 
     // Don't allow overwriting built-in presets
-    if presets::find_preset(name).is_some() {
+    if presets::find_preset(name).is_some() || presets::find_event_preset(name).is_some() {
         bail!(
             "Cannot overwrite built-in preset '{}'. Choose a different name.",
             name
         );
     }
+    if name == user_presets::MIXES_KEY {
+        bail!("'{}' is reserved. Choose a different name.", name);
+    }
+    if user_presets::find_mix(name)?.is_some() {
+        bail!(
+            "'{}' is already a saved mix. Choose a different name.",
+            name
+        );
+    }
 
-    // Generate a label from first 3 words
-    let label: String = prompt
-        .split_whitespace()
-        .take(3)
-        .collect::<Vec<&str>>()
-        .join(" ");
-    let label = if label.len() < prompt.len() {
-        format!("{}…", label)
-    } else {
-        label
+    let label = prompt_label(&prompt);
+
+    // Event presets are short one-shots rather than loops
+    let events = every.map(|(min, max)| EventSpec {
+        min_gap_secs: min.as_secs_f32(),
+        max_gap_secs: max.as_secs_f32(),
+        ..EventSpec::default()
+    });
+    let params = match events {
+        Some(spec) => {
+            spec.validate()?;
+            GenOverrides {
+                duration_seconds: params.duration_seconds.or(Some(DEFAULT_EVENT_SECONDS)),
+                loop_audio: Some(false),
+                ..params.clone()
+            }
+        }
+        None => params.clone(),
     };
+    let params = &params;
 
     let is_update = user_presets::find(name)?.is_some();
-    user_presets::save(name, "🎵", &label, &prompt)?;
+    let emoji = if events.is_some() { "✨" } else { "🎵" };
+    user_presets::save(
+        name,
+        UserPreset {
+            emoji: emoji.to_string(),
+            label,
+            prompt: prompt.clone(),
+            params: params.clone(),
+            events,
+            stereo,
+            tone,
+        },
+    )?;
 
     if is_update {
         println!("Updated preset '{}': {}", name, prompt);
     } else {
         println!("Saved preset '{}': {}", name, prompt);
     }
+    if !params.is_empty() {
+        println!("Generation settings: {}", params.describe());
+    }
+    if let Some(spec) = events {
+        println!("Events: {}", spec.describe());
+    }
+    if stereo != Stereo::default() {
+        println!("Stereo: {}", stereo.describe());
+    }
+    if !tone.is_flat() {
+        println!("EQ: {}", tone.describe());
+    }
     println!("\nUse it with: vibeband {}", name);
     Ok(())
 }
@@ -232,15 +777,66 @@ fn cmd_remove(args: &[String]) -> Result<()> {
 
     let name = &args[0];
 
-    if presets::find_preset(name).is_some() {
+    if presets::find_preset(name).is_some() || presets::find_event_preset(name).is_some() {
         bail!("Cannot remove built-in preset '{}'.", name);
     }
 
     if user_presets::remove(name)? {
         println!("Removed preset '{}'.", name);
+    } else if user_presets::remove_mix(name)? {
+        println!("Removed mix '{}'.", name);
     } else {
-        bail!("No custom preset named '{}'.", name);
+        bail!("No custom preset or mix named '{}'.", name);
+    }
+    Ok(())
+}
+
+fn cmd_mix_save(args: &[String], prompts: &[String], overrides: &GenOverrides) -> Result<()> {
+    if args.is_empty() || (args.len() == 1 && prompts.is_empty()) {
+        bail!("Usage: vibeband mix save <name> <scene>[@VOL]... [-c \"<prompt>\"]...\n\nExample:\n  vibeband mix save study cafe@40 rain@80 -c \"soft piano\"");
+    }
+
+    let name = &args[0];
+    check_mix_name(name)?;
+    let config = config::load()?;
+
+    let mut layers = Vec::new();
+    for arg in &args[1..] {
+        let (scene, volume) = parse_mix_layer(arg)?;
+        let volume = volume.unwrap_or(DEFAULT_LAYER_VOLUME);
+        let Some(found) = find_scene(scene, &config, overrides) else {
+            bail!("Unknown scene: '{}'.", scene);
+        };
+        layers.push(MixLayer {
+            source: MixSource::Scene(scene.to_string()),
+            volume,
+            stereo: found.stereo,
+            tone: found.tone,
+            params: overrides.clone(),
+        });
+    }
+    for prompt in prompts {
+        layers.push(MixLayer {
+            source: MixSource::Prompt(prompt.clone()),
+            volume: DEFAULT_LAYER_VOLUME,
+            stereo: Stereo::default(),
+            tone: Tone::default(),
+            params: GenParams::resolve(&config, overrides).to_overrides(),
+        });
     }
+
+    let count = layers.len();
+    let is_update = user_presets::find_mix(name)?.is_some();
+    user_presets::save_mix(
+        name,
+        Mix {
+            master_volume: DEFAULT_MASTER_VOLUME,
+            layers,
+        },
+    )?;
+    let verb = if is_update { "Updated" } else { "Saved" };
+    println!("{} mix '{}' with {} layers.", verb, name, count);
+    println!("\nPlay it with: vibeband {}", name);
     Ok(())
 }
 
@@ -250,6 +846,23 @@ fn cmd_list() -> Result<()> {
         println!("  {} {:<12} {}", p.emoji, p.name, p.label);
     }
 
+    println!("\nOffline noise:");
+    for kind in NOISE_KINDS {
+        let name = format!("noise:{}", kind.name());
+        println!("  {} {:<12} {}", kind.emoji(), name, kind.label());
+    }
+
+    println!("\nRandom events:");
+    for p in EVENT_PRESETS {
+        println!(
+            "  {} {:<12} {} ({})",
+            p.emoji,
+            p.name,
+            p.label,
+            p.spec.describe()
+        );
+    }
+
     let user = user_presets::load()?;
     if user.presets.is_empty() {
         println!("\nNo custom presets yet. Save one with:");
@@ -258,97 +871,1089 @@ fn cmd_list() -> Result<()> {
         println!("\nYour presets:");
         for (name, p) in &user.presets {
             println!("  {} {:<12} {} — {}", p.emoji, name, p.label, p.prompt);
+            if !p.params.is_empty() {
+                println!("  {:<15} ({})", "", p.params.describe());
+            }
+            if let Some(spec) = &p.events {
+                println!("  {:<15} (events {})", "", spec.describe());
+            }
+            if p.stereo != Stereo::default() {
+                println!("  {:<15} (stereo {})", "", p.stereo.describe());
+            }
+            if !p.tone.is_flat() {
+                println!("  {:<15} (EQ {})", "", p.tone.describe());
+            }
+        }
+    }
+
+    if !user.mixes.is_empty() {
+        println!("\nYour mixes:");
+        for (name, mix) in &user.mixes {
+            println!("  🎚 {:<12} {}", name, describe_mix(mix));
         }
     }
     Ok(())
 }
 
-async fn run_app(
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    scenes: Vec<Scene>,
-) -> Result<()> {
-    let engine = AudioEngine::new()?;
-    let total = scenes.len();
-    let mut app = App::new(engine, total);
-    let mut vis = Visualizer::new(44100.0);
-
-    let (tx, mut rx) = mpsc::channel::<LoadedLayer>(total);
-
-    let client = api::ElevenLabsClient::new()?;
-    let client = std::sync::Arc::new(client);
-
-    for scene in &scenes {
-        let tx = tx.clone();
-        let client = client.clone();
-        let prompt = scene.prompt.clone();
-        let name = scene.name.clone();
-        let emoji = scene.emoji.clone();
-        let label = scene.label.clone();
-
-        tokio::spawn(async move {
-            let result = match cache::read_cache(&prompt) {
-                Ok(Some(cached)) => Ok(cached),
-                _ => match client.generate_sound(&prompt).await {
-                    Ok(generated) => {
-                        let _ = cache::write_cache(&prompt, &generated);
-                        Ok(generated)
-                    }
-                    Err(e) => Err(e),
-                },
-            };
-            let _ = tx
-                .send(LoadedLayer {
-                    name,
-                    emoji,
-                    label,
-                    result,
+/// One-line summary like `cafe 40% + rain 80% + "soft piano" 25%`.
+fn describe_mix(mix: &Mix) -> String {
+    mix.layers
+        .iter()
+        .map(|l| {
+            let pct = (l.volume * 100.0).round();
+            match &l.source {
+                MixSource::Scene(name) => format!("{} {}%", name, pct),
+                MixSource::Prompt(prompt) => format!("\"{}\" {}%", prompt_label(prompt), pct),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn cmd_cache(args: &[String], overrides: &GenOverrides) -> Result<()> {
+    let usage = "Usage: vibeband cache <list|show|rm|prune|pin|unpin|stats>";
+    let Some(sub) = args.first() else {
+        bail!(usage);
+    };
+    match sub.as_str() {
+        "list" => cache_list(),
+        "show" => cache_show(&args[1..], overrides),
+        "rm" => cache_rm(&args[1..], overrides),
+        "prune" => cache_prune(&args[1..]),
+        "pin" => cache_pin(&args[1..], overrides, true),
+        "unpin" => cache_pin(&args[1..], overrides, false),
+        "stats" => cache_stats(),
+        other => bail!("Unknown cache command '{}'. {}", other, usage),
+    }
+}
+
+/// Accept a cache key, a unique key prefix, or a scene name (resolved with
+/// the same generation settings playback would use). Scenes and prefixes
+/// stand for every take of the request.
+fn resolve_cache_keys(arg: &str, overrides: &GenOverrides) -> Result<Vec<String>> {
+    let manifest = cache::load_manifest()?;
+    if manifest.entries.contains_key(arg)
+        || (cache::is_cache_key(arg) && cache::key_path(arg)?.exists())
+    {
+        return Ok(vec![arg.to_string()]);
+    }
+
+    let all_takes = |base: &str| -> Result<Vec<String>> {
+        let takes = cache::takes_for_key(base)?;
+        if takes.is_empty() {
+            // Nothing on disk; the base key still names any metadata
+            return Ok(vec![base.to_string()]);
+        }
+        Ok(takes.iter().map(|&n| cache::take_key(base, n)).collect())
+    };
+
+    if let Some(scene) = find_scene(arg, &config::load()?, overrides) {
+        return match scene.source {
+            SceneSource::Prompt(request) | SceneSource::Events { request, .. } => {
+                all_takes(&cache::cache_key(&request))
+            }
+            SceneSource::Noise(_) => bail!("'{}' is synthesized on the fly and never cached.", arg),
+        };
+    }
+
+    let mut bases: Vec<&str> = manifest
+        .entries
+        .keys()
+        .map(|k| cache::split_take_key(k).0)
+        .filter(|k| k.starts_with(arg))
+        .collect();
+    bases.dedup();
+    match bases.as_slice() {
+        [base] => all_takes(base),
+        [] => bail!("No cache entry or scene matching '{}'.", arg),
+        _ => bail!(
+            "'{}' matches {} cache entries; use more characters.",
+            arg,
+            bases.len()
+        ),
+    }
+}
+
+fn cache_list() -> Result<()> {
+    let manifest = cache::load_manifest()?;
+    let orphans = cache::orphan_blobs(&manifest)?;
+    if manifest.entries.is_empty() && orphans.is_empty() {
+        println!("Cache is empty.");
+        return Ok(());
+    }
+
+    let mut entries: Vec<_> = manifest.entries.iter().collect();
+    entries.sort_by_key(|(_, e)| std::cmp::Reverse(e.last_used_at));
+
+    println!(
+        "{:<18}  {:>8}  {:>6}  {:>9}  PROMPT",
+        "KEY", "SIZE", "LENGTH", "LAST USED"
+    );
+    let pin = |pinned: bool| if pinned { "📌 " } else { "" };
+    for (key, e) in entries {
+        let length = e
+            .audio_seconds
+            .map(|s| format!("{:.1}s", s))
+            .unwrap_or_else(|| "?".to_string());
+        let prompt: String = e.prompt.chars().take(40).collect();
+        let ellipsis = if prompt.len() < e.prompt.len() {
+            "…"
+        } else {
+            ""
+        };
+        println!(
+            "{:<18}  {:>8}  {:>6}  {:>9}  {}{}{}",
+            key,
+            format_bytes(e.bytes),
+            length,
+            format_ago(e.last_used_at),
+            pin(manifest.is_pinned(key)),
+            prompt,
+            ellipsis
+        );
+    }
+    for (key, bytes) in &orphans {
+        println!(
+            "{:<18}  {:>8}  {:>6}  {:>9}  (no metadata)",
+            key,
+            format_bytes(*bytes),
+            "?",
+            "?"
+        );
+    }
+    Ok(())
+}
+
+fn cache_show(args: &[String], overrides: &GenOverrides) -> Result<()> {
+    let Some(arg) = args.first() else {
+        bail!("Usage: vibeband cache show <key|scene>");
+    };
+    let manifest = cache::load_manifest()?;
+    for (i, key) in resolve_cache_keys(arg, overrides)?.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        show_entry(&manifest, key)?;
+    }
+    Ok(())
+}
+
+fn show_entry(manifest: &cache::Manifest, key: &str) -> Result<()> {
+    let path = cache::key_path(key)?;
+    if !path.exists() && !manifest.entries.contains_key(key) {
+        println!("Nothing cached for {} yet.", key);
+        return Ok(());
+    }
+
+    let (base, take) = cache::split_take_key(key);
+    let selected = manifest.selected_takes.get(base).copied().unwrap_or(1);
+    println!("Key:        {}", key);
+    println!("File:       {}", path.display());
+    println!(
+        "Take:       {}{}",
+        take,
+        if take == selected { " (selected)" } else { "" }
+    );
+    match manifest.entries.get(key) {
+        Some(e) => {
+            let p = &e.params;
+            println!("Prompt:     {}", e.prompt);
+            println!("Model:      {}", p.model_id);
+            println!(
+                "Requested:  {}s, loop {}",
+                p.duration_seconds,
+                if p.loop_audio { "on" } else { "off" }
+            );
+            if let Some(influence) = p.prompt_influence {
+                println!("Influence:  {}", influence);
+            }
+            if let Some(secs) = e.audio_seconds {
+                println!("Length:     {:.1}s", secs);
+            }
+            if let Some(lufs) = e.loudness_lufs {
+                println!("Loudness:   {:.1} LUFS", lufs);
+            }
+            println!("Size:       {}", format_bytes(e.bytes));
+            println!("Created:    {}", format_ago(e.created_at));
+            println!("Last used:  {}", format_ago(e.last_used_at));
+            if manifest.is_pinned(key) {
+                println!("Pinned:     yes (no take is ever evicted)");
+            }
+        }
+        None => println!("No metadata recorded (cached before the manifest existed)."),
+    }
+    if !path.exists() {
+        println!("\nThe audio file is missing; `vibeband cache prune` will drop this entry.");
+    }
+    Ok(())
+}
+
+fn cache_rm(args: &[String], overrides: &GenOverrides) -> Result<()> {
+    if args.is_empty() {
+        bail!("Usage: vibeband cache rm <key|scene>...");
+    }
+    for arg in args {
+        for key in resolve_cache_keys(arg, overrides)? {
+            if cache::remove_entry(&key)? {
+                println!("Removed {}.", key);
+            } else {
+                println!("Nothing cached for {}.", key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Drop manifest entries whose audio is gone, then evict down to the
+/// configured limits. An explicit age (e.g. `30d`) replaces the configured one.
+fn cache_prune(args: &[String]) -> Result<()> {
+    let limits = config::load()?.cache;
+    let max_age = match args.first() {
+        Some(arg) => Some(parse_span(arg).map_err(anyhow::Error::msg)?),
+        None => limits.max_age(),
+    };
+
+    let manifest = cache::load_manifest()?;
+    let mut dangling = 0;
+    for key in manifest.entries.keys() {
+        if !cache::key_path(key)?.exists() {
+            cache::remove_entry(key)?;
+            dangling += 1;
+        }
+    }
+
+    let evicted = cache::evict(limits.max_bytes(), max_age, None)?;
+    println!(
+        "Removed {} clips, freed {}.",
+        evicted.count,
+        format_bytes(evicted.bytes)
+    );
+    if dangling > 0 {
+        println!("Dropped {} entries whose audio file was missing.", dangling);
+    }
+    let temps = cache::remove_temp_files()?;
+    if temps > 0 {
+        println!("Deleted {} leftover partial writes.", temps);
+    }
+    Ok(())
+}
+
+fn cache_pin(args: &[String], overrides: &GenOverrides, pinned: bool) -> Result<()> {
+    if args.is_empty() {
+        let verb = if pinned { "pin" } else { "unpin" };
+        bail!("Usage: vibeband cache {} <key|scene>...", verb);
+    }
+    for arg in args {
+        let mut bases: Vec<String> = Vec::new();
+        for key in resolve_cache_keys(arg, overrides)? {
+            let base = cache::set_pinned(&key, pinned)?;
+            if !bases.contains(&base) {
+                bases.push(base);
+            }
+        }
+        for base in bases {
+            if pinned {
+                println!(
+                    "Pinned {} ({}); none of its takes will be evicted.",
+                    arg, base
+                );
+            } else {
+                println!("Unpinned {} ({}).", arg, base);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cache_stats() -> Result<()> {
+    let manifest = cache::load_manifest()?;
+    let orphans = cache::orphan_blobs(&manifest)?;
+    let entries = manifest.entries.values();
+
+    let bytes: u64 =
+        entries.clone().map(|e| e.bytes).sum::<u64>() + orphans.iter().map(|(_, b)| b).sum::<u64>();
+    let audio = entries
+        .clone()
+        .filter_map(|e| e.audio_seconds)
+        .fold(0.0, |acc, s| acc + s);
+    let oldest = entries.clone().map(|e| e.created_at).min();
+    let newest = entries.map(|e| e.created_at).max();
+
+    println!("Location:   {}", cache::cache_dir()?.display());
+    println!("Clips:      {}", manifest.entries.len() + orphans.len());
+    if !orphans.is_empty() {
+        println!("  without metadata: {}", orphans.len());
+    }
+    println!("Size:       {}", format_bytes(bytes));
+    let seams: u64 = cache::seam_bytes()?.values().sum();
+    if seams > 0 {
+        println!("  loop seams: {}", format_bytes(seams));
+    }
+    println!("Audio:      {:.1} min", audio / 60.0);
+    if let (Some(oldest), Some(newest)) = (oldest, newest) {
+        println!("Oldest:     {}", format_ago(oldest));
+        println!("Newest:     {}", format_ago(newest));
+    }
+    Ok(())
+}
+
+/// Control a running vibeband, the TUI or a daemon, over its socket.
+async fn cmd_ctl(args: &[String], prompts: &[String], json: bool) -> Result<()> {
+    let usage = "Usage: vibeband ctl <status|play|pause|toggle|vol|layer|add|remove|mute|solo|reroll|take|sleep|save|quit>";
+    let Some(sub) = args.first() else {
+        bail!(usage);
+    };
+    let arg = |i: usize, usage: &str| match args.get(i) {
+        Some(arg) => Ok(arg.as_str()),
+        None => Err(anyhow::anyhow!("Usage: vibeband ctl {} {}", sub, usage)),
+    };
+
+    let mut client = control::Client::connect().await?;
+    let commands = match sub.as_str() {
+        "status" => {
+            let status = client.status().await?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_status(&status);
+            }
+            return Ok(());
+        }
+        "play" => vec![Command::Play],
+        "pause" => vec![Command::Pause],
+        "toggle" => vec![Command::Toggle],
+        "vol" => {
+            let level = arg(1, "<N|+N|-N>")?;
+            let volume = parse_level(level, client.status().await?.master_volume)?;
+            vec![Command::Master { volume }]
+        }
+        "layer" => {
+            let (layer, level) = (arg(1, "<name|n> <N|+N|-N>")?, arg(2, "<name|n> <N|+N|-N>")?);
+            let status = client.status().await?;
+            let layer = ctl_layer(layer, &status)?;
+            let volume = parse_level(level, layer.volume)?;
+            vec![Command::Volume {
+                layer: LayerRef::Name(layer.name.clone()),
+                volume,
+            }]
+        }
+        "add" => {
+            let mut commands: Vec<Command> = args[1..]
+                .iter()
+                .map(|scene| Command::Add {
+                    scene: scene.clone(),
                 })
-                .await;
+                .collect();
+            commands.extend(prompts.iter().map(|prompt| Command::AddPrompt {
+                prompt: prompt.clone(),
+            }));
+            if commands.is_empty() {
+                bail!("Usage: vibeband ctl add <scene>... [-c \"<prompt>\"]");
+            }
+            commands
+        }
+        "remove" | "mute" | "solo" | "reroll" | "take" => {
+            let layer = arg(1, "<name|n>")?;
+            let layer = LayerRef::Name(ctl_layer(layer, &client.status().await?)?.name.clone());
+            vec![match sub.as_str() {
+                "remove" => Command::Remove { layer },
+                "mute" => Command::Mute { layer },
+                "solo" => Command::Solo { layer },
+                "reroll" => Command::Reroll { layer },
+                _ => Command::NextTake { layer },
+            }]
+        }
+        "sleep" => {
+            let secs = match arg(1, "<TIME|off>")? {
+                "off" => None,
+                span => Some(parse_span(span).map_err(anyhow::Error::msg)?.as_secs()),
+            };
+            vec![Command::Sleep { secs }]
+        }
+        "save" => vec![Command::SaveMix {
+            name: arg(1, "<name>")?.to_string(),
+        }],
+        "quit" => vec![Command::Quit],
+        other => bail!("Unknown ctl command '{}'. {}", other, usage),
+    };
+    for command in &commands {
+        client.send(command).await?;
+    }
+    Ok(())
+}
+
+/// A layer given to `ctl` by its position in `ctl status`, from 1, or by
+/// its scene name.
+fn ctl_layer<'a>(arg: &str, status: &'a Status) -> Result<&'a LayerStatus> {
+    let Some(layer) = status.layer(arg) else {
+        bail!("No layer '{}' is playing. See `vibeband ctl status`.", arg);
+    };
+    Ok(layer)
+}
+
+fn print_status(status: &Status) {
+    let state = match &status.state {
+        AppState::Loading { done, total } => format!("Loading {}/{}", done, total),
+        AppState::Playing => "Playing".to_string(),
+        AppState::Paused => "Paused".to_string(),
+    };
+    println!(
+        "{}, master volume {}%",
+        state,
+        (status.master_volume * 100.0).round()
+    );
+    if let Some(secs) = status.sleep_secs {
+        println!("Sleeping in {} min", secs.div_ceil(60));
+    }
+    for (i, layer) in status.layers.iter().enumerate() {
+        let mut flags = Vec::new();
+        if layer.muted {
+            flags.push("muted".to_string());
+        }
+        if layer.soloed {
+            flags.push("solo".to_string());
+        }
+        let stereo = Stereo {
+            pan: layer.pan,
+            width: layer.width,
+        };
+        if stereo != Stereo::default() {
+            flags.push(stereo.describe());
+        }
+        if !layer.tone.is_flat() {
+            flags.push(format!("EQ {}", layer.tone.describe()));
+        }
+        if layer.rerolling {
+            flags.push("re-rolling".to_string());
+        }
+        println!(
+            "{:>3}. {} {:<14} {:>3}%  {}",
+            i + 1,
+            layer.emoji,
+            layer.name,
+            (layer.volume * 100.0).round(),
+            flags.join(", ")
+        );
+    }
+    for pending in &status.pending {
+        println!("     {} {:<14} loading", pending.emoji, pending.name);
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    let b = bytes as f64;
+    if b >= 1024.0 * 1024.0 * 1024.0 {
+        format!("{:.1} GB", b / (1024.0 * 1024.0 * 1024.0))
+    } else if b >= 1024.0 * 1024.0 {
+        format!("{:.1} MB", b / (1024.0 * 1024.0))
+    } else if b >= 1024.0 {
+        format!("{:.0} KB", b / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+fn format_ago(unix_secs: u64) -> String {
+    let secs = cache::unix_now().saturating_sub(unix_secs);
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Fetch the selected cached take of `request`, generating a new take on a
+/// miss or when `fresh` is set. A new take that can't be cached still plays,
+/// with a notice through `tx`.
+async fn load_clip(
+    request: SoundRequest,
+    fresh: bool,
+    generator: &dyn SoundGenerator,
+    config: &Config,
+    progress: Progress<'_>,
+    tx: &mpsc::UnboundedSender<LoadEvent>,
+) -> Result<(Vec<u8>, ClipTake)> {
+    // Reads verify checksums and may measure loudness, writes measure it
+    let cached = if fresh {
+        None
+    } else {
+        let request = request.clone();
+        tokio::task::spawn_blocking(move || cache::read_cache(&request))
+            .await?
+            .ok()
+            .flatten()
+    };
+    let (take, data) = match cached {
+        Some(cached) => (Some(cached.number), cached.data),
+        None => {
+            let generated = generator.generate(&request, progress).await?;
+            let (request, limits) = (request.clone(), config.cache.clone());
+            let (written, generated) = tokio::task::spawn_blocking(move || {
+                let written = cache::write_cache(&request, &generated, &limits);
+                (written, generated)
+            })
+            .await?;
+            match written {
+                Ok(take) => (Some(take), generated),
+                Err(e) => {
+                    let notice = format!("Playing without caching it: {:#}", e);
+                    let _ = tx.send(LoadEvent::Notice(notice));
+                    (None, generated)
+                }
+            }
+        }
+    };
+    let data = if config.audio.crossfade_seams {
+        let (request, audio) = (request.clone(), config.audio.clone());
+        tokio::task::spawn_blocking(move || looped_clip(&request, take, data, &audio)).await?
+    } else {
+        data
+    };
+    let takes = cache::takes(&request).unwrap_or_else(|_| take.into_iter().collect());
+    Ok((
+        data,
+        ClipTake {
+            request,
+            take,
+            takes,
+        },
+    ))
+}
+
+/// Cached takes of an event request, generating more until there are
+/// `spec.variations`. Plays with fewer if some fail to generate.
+async fn load_events(
+    request: SoundRequest,
+    spec: EventSpec,
+    generator: &dyn SoundGenerator,
+    config: &Config,
+    progress: Progress<'_>,
+) -> Result<LayerSource> {
+    spec.validate()?;
+    let mut clips = Vec::new();
+    for take in cache::takes(&request).unwrap_or_default() {
+        if let Ok(Some(data)) = cache::read_take(&request, take) {
+            clips.push(data);
+        }
+    }
+    clips.truncate(spec.variations as usize);
+    while clips.len() < spec.variations as usize {
+        match generator.generate(&request, progress).await {
+            Ok(data) => {
+                let _ = cache::write_cache(&request, &data, &config.cache);
+                clips.push(data);
+            }
+            Err(e) if clips.is_empty() => return Err(e),
+            Err(_) => break,
+        }
+    }
+    Ok(LayerSource::Events { clips, spec })
+}
+
+/// The audio a layer loops for a take: crossfaded at the seam if enabled.
+fn looped_clip(
+    request: &SoundRequest,
+    take: Option<u32>,
+    data: Vec<u8>,
+    audio: &AudioConfig,
+) -> Vec<u8> {
+    if !audio.crossfade_seams {
+        return data;
+    }
+    let crossfade = Duration::from_millis(audio.seam_crossfade_ms);
+    seam::seamless(request, take, data, crossfade)
+}
+
+fn retry_status(progress: GenProgress) -> RetryStatus {
+    let GenProgress::Retrying {
+        retry,
+        max_retries,
+        delay,
+    } = progress;
+    RetryStatus {
+        retry,
+        max_retries,
+        starts_at: Instant::now() + delay,
+    }
+}
+
+/// Generate a new take for a layer in the background. Noise layers and
+/// layers already re-rolling are left alone.
+fn reroll(
+    app: &mut App,
+    index: usize,
+    generator: &Arc<dyn SoundGenerator>,
+    config: &Config,
+    tx: &mpsc::UnboundedSender<LoadEvent>,
+) {
+    let Some(layer) = app.engine.layers.get(index) else {
+        return;
+    };
+    let Some(clip) = &layer.clip else {
+        return;
+    };
+    if app.rerolling.contains_key(&layer.id) {
+        return;
+    }
+    let id = layer.id;
+    let request = clip.request.clone();
+    app.rerolling.insert(id, None);
+
+    let tx = tx.clone();
+    let generator = generator.clone();
+    let config = config.clone();
+    tokio::spawn(async move {
+        let progress = |p: GenProgress| {
+            let _ = tx.send(LoadEvent::RerollRetrying {
+                id,
+                status: retry_status(p),
+            });
+        };
+        let result = load_clip(request, true, generator.as_ref(), &config, &progress, &tx)
+            .await
+            .map(|(data, clip)| {
+                let loudness = clip
+                    .take
+                    .and_then(|take| cache::take_loudness(&clip.request, take));
+                (data, clip, loudness)
+            });
+        let _ = tx.send(LoadEvent::Rerolled { id, result });
+    });
+}
+
+/// Switch a layer to the cached take after the one it plays, wrapping
+/// around, and remember the choice for next time. Reading and crossfading
+/// the take happens in the background, reporting back through `tx`.
+fn next_take(
+    app: &mut App,
+    index: usize,
+    audio: &AudioConfig,
+    tx: &mpsc::UnboundedSender<LoadEvent>,
+) {
+    let Some(layer) = app.engine.layers.get(index) else {
+        return;
+    };
+    let Some(clip) = &layer.clip else {
+        return;
+    };
+    // Another press before the last switch lands would pick the same take
+    if !app.switching.insert(layer.id) {
+        return;
+    }
+    let (id, request, current) = (layer.id, clip.request.clone(), clip.take);
+
+    let (tx, audio) = (tx.clone(), audio.clone());
+    tokio::task::spawn_blocking(move || {
+        let result = read_next_take(request, current, &audio);
+        let _ = tx.send(LoadEvent::TakeSwitched { id, result });
+    });
+}
+
+/// Read, crossfade and select the take after `current`. `None` when there
+/// is no other take.
+fn read_next_take(
+    request: SoundRequest,
+    current: Option<u32>,
+    audio: &AudioConfig,
+) -> Result<Option<NewTake>> {
+    let takes = cache::takes(&request)?;
+    let next = takes
+        .iter()
+        .copied()
+        .find(|&n| n > current.unwrap_or(0))
+        .or_else(|| takes.first().copied());
+    let Some(next) = next.filter(|&n| Some(n) != current) else {
+        return Ok(None);
+    };
+    let Some(data) = cache::read_take(&request, next)? else {
+        bail!("take {} was corrupt and has been set aside", next);
+    };
+    let data = looped_clip(&request, Some(next), data, audio);
+    let loudness = cache::take_loudness(&request, next);
+    cache::select_take(&request, next)?;
+    let takes = cache::takes(&request)?;
+    let clip = ClipTake {
+        request,
+        take: Some(next),
+        takes,
+    };
+    Ok(Some((data, clip, loudness)))
+}
+
+/// Play a new take on the layer with this id, if it is still there.
+fn replace_clip(app: &mut App, id: u64, result: Result<NewTake>) {
+    let Some(index) = app.engine.layer_index(id) else {
+        return;
+    };
+    let result = result.and_then(|(data, clip, loudness)| {
+        app.engine
+            .replace_source(index, LayerSource::Clip(data), loudness)?;
+        app.engine.layers[index].clip = Some(clip);
+        Ok(())
+    });
+    if let Err(e) = result {
+        let label = app.engine.layers[index].label.clone();
+        app.load_error(format!("{}: {}", label, e));
+    }
+}
+
+/// Queue a scene as a pending layer and read or generate its audio in the
+/// background, reporting back through `tx`.
+fn spawn_load(
+    app: &mut App,
+    scene: Scene,
+    generator: &Arc<dyn SoundGenerator>,
+    config: &Config,
+    tx: &mpsc::UnboundedSender<LoadEvent>,
+) {
+    let tx = tx.clone();
+    let generator = generator.clone();
+    let config = config.clone();
+    let Scene {
+        name,
+        emoji,
+        label,
+        source,
+        volume,
+        stereo,
+        tone,
+    } = scene;
+    app.layer_queued(&name, &emoji, &label);
+
+    tokio::spawn(async move {
+        let progress = |p: GenProgress| {
+            let _ = tx.send(LoadEvent::Retrying {
+                name: name.clone(),
+                status: retry_status(p),
+            });
+        };
+        let result = match source {
+            SceneSource::Noise(kind) => Ok((LayerSource::Noise(kind), None, kind.loudness())),
+            // Events are accents; their spec sets how loud they play
+            SceneSource::Events { request, spec } => {
+                load_events(request, spec, generator.as_ref(), &config, &progress)
+                    .await
+                    .map(|source| (source, None, None))
+            }
+            SceneSource::Prompt(request) => {
+                load_clip(request, false, generator.as_ref(), &config, &progress, &tx)
+                    .await
+                    .map(|(data, clip)| {
+                        let loudness = clip
+                            .take
+                            .and_then(|take| cache::take_loudness(&clip.request, take));
+                        (LayerSource::Clip(data), Some(clip), loudness)
+                    })
+            }
+        };
+        let _ = tx.send(LoadEvent::Loaded(LoadedLayer {
+            name: name.clone(),
+            emoji,
+            label,
+            volume,
+            stereo,
+            tone,
+            result,
+        }));
+    });
+}
+
+/// A playing mix with its loaders and control socket. The TUI and the
+/// daemon both drive one, a tick at a time.
+struct Session<'a> {
+    app: App,
+    vis: Visualizer,
+    /// Band levels from the last tick.
+    spectrum: Vec<f64>,
+    config: &'a Config,
+    overrides: &'a GenOverrides,
+    generator: Arc<dyn SoundGenerator>,
+    tx: mpsc::UnboundedSender<LoadEvent>,
+    rx: mpsc::UnboundedReceiver<LoadEvent>,
+    custom_count: usize,
+    /// Commands from the control socket and desktop integrations.
+    requests: mpsc::UnboundedReceiver<control::Request>,
+    /// The latest status, for integrations that follow playback.
+    updates: watch::Sender<Status>,
+}
+
+impl<'a> Session<'a> {
+    /// Start loading `scenes`, taking commands from `requests`.
+    fn new(
+        config: &'a Config,
+        overrides: &'a GenOverrides,
+        scenes: Vec<Scene>,
+        master_volume: Option<f32>,
+        sleep: Option<Duration>,
+        requests: mpsc::UnboundedReceiver<control::Request>,
+    ) -> Result<Self> {
+        let mut engine = AudioEngine::new(&config.audio)?;
+        if let Some(volume) = master_volume {
+            engine.set_master_volume(volume);
+        }
+        let custom_count = scenes
+            .iter()
+            .filter(|s| s.name.starts_with("custom_"))
+            .count();
+        let mut app = App::new(engine, scenes.len());
+        app.set_sleep(sleep)?;
+
+        let (tx, rx) = mpsc::unbounded_channel::<LoadEvent>();
+        let generator = generator::from_config(config);
+        for scene in scenes {
+            spawn_load(&mut app, scene, &generator, config, &tx);
+        }
+
+        let (updates, _) = watch::channel(app.status(&[]));
+        Ok(Self {
+            app,
+            vis: Visualizer::new(44100.0),
+            spectrum: Vec::new(),
+            config,
+            overrides,
+            generator,
+            tx,
+            rx,
+            custom_count,
+            requests,
+            updates,
+        })
+    }
+
+    fn status(&self) -> Status {
+        self.app.status(&self.spectrum)
+    }
+
+    /// Follow the status as it changes, once a tick.
+    fn subscribe(&self) -> watch::Receiver<Status> {
+        self.updates.subscribe()
+    }
+
+    /// Carry out a command from the TUI or a control client.
+    fn handle(&mut self, command: Command) -> Reply {
+        let wants_status = matches!(command, Command::Status);
+        let result = self.app.apply(command).and_then(|_| {
+            for action in std::mem::take(&mut self.app.actions) {
+                self.run_action(action)?;
+            }
+            Ok(())
         });
+        match result {
+            Ok(()) if wants_status => Reply {
+                status: Some(self.status()),
+                ..Reply::ok()
+            },
+            Ok(()) => Reply::ok(),
+            Err(e) => Reply::error(e.to_string()),
+        }
+    }
+
+    fn run_action(&mut self, action: Action) -> Result<()> {
+        let (app, config, overrides) = (&mut self.app, self.config, self.overrides);
+        match action {
+            Action::Reroll(index) => reroll(app, index, &self.generator, config, &self.tx),
+            Action::NextTake(index) => next_take(app, index, &config.audio, &self.tx),
+            Action::AddScene(name) => {
+                if app.has_layer(&name) {
+                    return Ok(());
+                }
+                match find_scene(&name, config, overrides) {
+                    Some(scene) => spawn_load(app, scene, &self.generator, config, &self.tx),
+                    None => bail!("Unknown scene: '{}'", name),
+                }
+            }
+            Action::AddPrompt(prompt) => {
+                self.custom_count += 1;
+                let scene = custom_scene(self.custom_count, &prompt, config, overrides);
+                spawn_load(app, scene, &self.generator, config, &self.tx);
+            }
+            Action::SaveMix(name) => {
+                check_mix_name(&name)?;
+                let mix = snapshot_mix(app, config, overrides);
+                let count = mix.layers.len();
+                user_presets::save_mix(&name, mix)?;
+                app.notify(format!("Saved mix '{}' ({} layers)", name, count));
+            }
+            Action::RemoveLayer(index) => {
+                if let Some(layer) = app.engine.remove_layer(index) {
+                    self.vis.remove_ring(&layer.ring);
+                    app.rerolling.remove(&layer.id);
+                    app.switching.remove(&layer.id);
+                }
+            }
+        }
+        Ok(())
     }
-    drop(tx);
 
-    let tick_rate = Duration::from_millis(50);
-    let mut loading_done = false;
+    /// Answer control clients, take in loaded audio and update timers and
+    /// the spectrum. Call once per frame.
+    fn tick(&mut self) -> Result<()> {
+        let app = &mut self.app;
+        app.tick_sleep();
+        app.tick_meter();
 
+        while let Ok((command, reply)) = self.requests.try_recv() {
+            let _ = reply.send(self.handle(command));
+        }
+
+        let (app, vis) = (&mut self.app, &mut self.vis);
+        app.engine.reap();
+        for layer in &app.engine.layers {
+            vis.set_audible(&layer.ring, app.engine.is_audible(layer));
+        }
+
+        // The sender is kept for re-rolls, so the channel never disconnects
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                LoadEvent::Notice(notice) => app.notify(notice),
+                LoadEvent::Retrying { name, status } => app.layer_retrying(&name, status),
+                LoadEvent::Loaded(layer) => match layer.result {
+                    Ok((source, clip, loudness)) => {
+                        let volume = layer.volume.unwrap_or(DEFAULT_LAYER_VOLUME);
+                        app.engine.add_layer(
+                            &layer.name,
+                            &layer.emoji,
+                            &layer.label,
+                            source,
+                            volume,
+                            loudness,
+                        )?;
+                        if let Some(last) = app.engine.layers.last_mut() {
+                            last.clip = clip;
+                            vis.add_ring(last.ring.clone());
+                        }
+                        let index = app.engine.layers.len() - 1;
+                        app.engine.set_stereo(index, layer.stereo);
+                        app.engine.set_tone(index, layer.tone);
+                        app.layer_loaded(&layer.name);
+                    }
+                    Err(e) => {
+                        app.load_error(format!("{}: {}", layer.label, e));
+                        app.layer_loaded(&layer.name);
+                    }
+                },
+                LoadEvent::RerollRetrying { id, status } => {
+                    if let Some(retry) = app.rerolling.get_mut(&id) {
+                        *retry = Some(status);
+                    }
+                }
+                LoadEvent::Rerolled { id, result } => {
+                    app.rerolling.remove(&id);
+                    replace_clip(app, id, result);
+                }
+                LoadEvent::TakeSwitched { id, result } => {
+                    app.switching.remove(&id);
+                    if let Some(result) = result.transpose() {
+                        replace_clip(app, id, result);
+                    }
+                }
+            }
+        }
+
+        self.spectrum = vis.analyze().to_vec();
+        if self.updates.receiver_count() > 0 {
+            self.updates.send_replace(self.status());
+        }
+        Ok(())
+    }
+
+    /// Fade everything out before the process exits.
+    async fn finish(&mut self) {
+        let fade = self.app.engine.fade_out();
+        tokio::time::sleep(fade).await;
+    }
+}
+
+/// Play in the terminal. Other vibeband commands can control the session
+/// through the control socket while it runs.
+async fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    mut session: Session<'_>,
+) -> Result<()> {
+    let mut ui = Ui::new();
     loop {
-        terminal.draw(|f| tui::draw(f, &app, &mut vis))?;
+        let mut status = session.status();
+        ui.clamp_selection(&status);
+        terminal.draw(|f| tui::draw(f, &status, &ui))?;
 
         while event::poll(Duration::ZERO)? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
-                    app.on_key(key);
+                    ui.on_key(key, &status);
+                    for command in std::mem::take(&mut ui.commands) {
+                        if let Some(error) = session.handle(command).error {
+                            ui.notify(error);
+                        }
+                    }
+                    status = session.status();
                 }
             }
         }
 
-        if app.should_quit {
+        session.tick()?;
+        if ui.should_quit || session.app.should_quit {
+            session.finish().await;
             return Ok(());
         }
 
-        if !loading_done {
-            match rx.try_recv() {
-                Ok(layer) => match layer.result {
-                    Ok(data) => {
-                        app.engine
-                            .add_layer(&layer.name, &layer.emoji, &layer.label, data)?;
-                        if let Some(last) = app.engine.layers.last() {
-                            vis.add_ring(last.ring.clone());
+        tokio::time::sleep(TICK_RATE).await;
+    }
+}
+
+/// Show the TUI for a session playing in a daemon. Quitting detaches and
+/// leaves it playing.
+async fn run_attached(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    mut client: control::Client,
+) -> Result<()> {
+    let mut ui = Ui::new();
+    loop {
+        let mut status = client.status().await?;
+        ui.clamp_selection(&status);
+        terminal.draw(|f| tui::draw(f, &status, &ui))?;
+
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    ui.on_key(key, &status);
+                    for command in std::mem::take(&mut ui.commands) {
+                        if let Err(e) = client.send(&command).await {
+                            ui.notify(e.to_string());
                         }
-                        app.layer_loaded();
                     }
-                    Err(e) => {
-                        app.load_error(format!("{}: {}", layer.label, e));
-                        app.layer_loaded();
-                    }
-                },
-                Err(mpsc::error::TryRecvError::Disconnected) => {
-                    loading_done = true;
+                    status = client.status().await?;
                 }
-                Err(mpsc::error::TryRecvError::Empty) => {}
             }
         }
 
-        tokio::time::sleep(tick_rate).await;
+        if ui.should_quit {
+            return Ok(());
+        }
+        tokio::time::sleep(TICK_RATE).await;
+    }
+}
+
+/// Play without a terminal until told to quit over the control socket,
+/// interrupted, or the sleep timer runs out.
+async fn cmd_daemon(mut session: Session<'_>) -> Result<()> {
+    eprintln!(
+        "vibeband daemon listening on {}",
+        control::socket_path()?.display()
+    );
+    loop {
+        session.tick()?;
+        for error in std::mem::take(&mut session.app.errors) {
+            eprintln!("{}", error);
+        }
+        if session.app.should_quit {
+            session.finish().await;
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(TICK_RATE) => {}
+            _ = tokio::signal::ctrl_c() => session.app.should_quit = true,
+        }
     }
 }
@@ -0,0 +1,290 @@
+use crate::control::{self, AppState, Command, Request, Status};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use zbus::fdo::{self, RequestNameFlags, RequestNameReply};
+use zbus::interface;
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::Connection;
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.vibeband";
+const TRACK_ID: &str = "/org/vibeband/mix";
+/// How often to check for changes worth signalling to desktop widgets.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keeps vibeband on the bus as an MPRIS media player until dropped.
+pub struct Mpris {
+    _connection: Connection,
+    watcher: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Mpris {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Show up in desktop media controls, passing their commands on to `tx`.
+pub async fn serve(tx: mpsc::UnboundedSender<Request>) -> Result<Mpris> {
+    let connection = Connection::session()
+        .await
+        .context("no D-Bus session bus")?;
+    serve_on(connection, tx).await
+}
+
+/// Like `serve`, on a bus that is already connected.
+pub async fn serve_on(connection: Connection, tx: mpsc::UnboundedSender<Request>) -> Result<Mpris> {
+    let server = connection.object_server();
+    server.at(PATH, Root { tx: tx.clone() }).await?;
+    server.at(PATH, Player { tx: tx.clone() }).await?;
+    // A second vibeband gets a name of its own
+    let reply = connection
+        .request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into())
+        .await;
+    if !matches!(reply, Ok(RequestNameReply::PrimaryOwner)) {
+        let name = format!("{}.instance{}", BUS_NAME, std::process::id());
+        connection
+            .request_name(name.as_str())
+            .await
+            .with_context(|| format!("failed to take the bus name {}", name))?;
+    }
+
+    let player = server.interface::<_, Player>(PATH).await?;
+    let watcher = tokio::spawn(async move {
+        let mut last = None;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let Ok(status) = status(&tx).await else {
+                break;
+            };
+            let now = (
+                playback_status(&status),
+                status.master_volume as f64,
+                title(&status),
+            );
+            let Some(before) = last.replace(now.clone()) else {
+                continue;
+            };
+            let emitter = player.signal_emitter();
+            let iface = player.get().await;
+            if before.0 != now.0 {
+                let _ = iface.playback_status_changed(emitter).await;
+            }
+            if before.1 != now.1 {
+                let _ = iface.volume_changed(emitter).await;
+            }
+            if before.2 != now.2 {
+                let _ = iface.metadata_changed(emitter).await;
+            }
+        }
+    });
+
+    Ok(Mpris {
+        _connection: connection,
+        watcher,
+    })
+}
+
+async fn send(tx: &mpsc::UnboundedSender<Request>, command: Command) -> fdo::Result<()> {
+    let reply = control::request(tx, command)
+        .await
+        .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+    match reply.error {
+        Some(error) => Err(fdo::Error::Failed(error)),
+        None => Ok(()),
+    }
+}
+
+async fn status(tx: &mpsc::UnboundedSender<Request>) -> fdo::Result<Status> {
+    control::request(tx, Command::Status)
+        .await
+        .ok()
+        .and_then(|reply| reply.status)
+        .ok_or_else(|| fdo::Error::Failed("vibeband stopped".to_string()))
+}
+
+/// Layers still loading count as playing: they start as soon as they're in.
+fn playback_status(status: &Status) -> String {
+    match status.state {
+        AppState::Loading { .. } | AppState::Playing => "Playing",
+        AppState::Paused => "Paused",
+    }
+    .to_string()
+}
+
+/// The mix's title: the labels of the layers playing.
+fn title(status: &Status) -> String {
+    let labels: Vec<&str> = status.layers.iter().map(|l| l.label.as_str()).collect();
+    match labels.is_empty() {
+        true => "vibeband".to_string(),
+        false => labels.join(" + "),
+    }
+}
+
+/// `org.mpris.MediaPlayer2`: who we are.
+struct Root {
+    tx: mpsc::UnboundedSender<Request>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    async fn raise(&self) {}
+
+    async fn quit(&self) -> fdo::Result<()> {
+        send(&self.tx, Command::Quit).await
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "vibeband"
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`: the whole mix is one endless track.
+struct Player {
+    tx: mpsc::UnboundedSender<Request>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn play_pause(&self) -> fdo::Result<()> {
+        send(&self.tx, Command::Toggle).await
+    }
+
+    async fn play(&self) -> fdo::Result<()> {
+        send(&self.tx, Command::Play).await
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        send(&self.tx, Command::Pause).await
+    }
+
+    /// There's nothing to rewind to, so stopping just pauses.
+    async fn stop(&self) -> fdo::Result<()> {
+        send(&self.tx, Command::Pause).await
+    }
+
+    async fn next(&self) {}
+
+    async fn previous(&self) {}
+
+    async fn seek(&self, _offset: i64) {}
+
+    async fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    async fn open_uri(&self, _uri: &str) {}
+
+    #[zbus(property)]
+    async fn playback_status(&self) -> fdo::Result<String> {
+        Ok(playback_status(&status(&self.tx).await?))
+    }
+
+    #[zbus(property)]
+    async fn volume(&self) -> fdo::Result<f64> {
+        Ok(status(&self.tx).await?.master_volume as f64)
+    }
+
+    #[zbus(property)]
+    async fn set_volume(&self, volume: f64) -> fdo::Result<()> {
+        if !volume.is_finite() {
+            return Err(fdo::Error::InvalidArgs(format!("bad volume {}", volume)));
+        }
+        let volume = volume.clamp(0.0, 1.0) as f32;
+        send(&self.tx, Command::Master { volume }).await
+    }
+
+    #[zbus(property)]
+    async fn metadata(&self) -> fdo::Result<HashMap<String, OwnedValue>> {
+        let title = title(&status(&self.tx).await?);
+        let track_id = ObjectPath::from_static_str_unchecked(TRACK_ID);
+        let values = [
+            ("mpris:trackid", Value::from(track_id)),
+            ("xesam:title", Value::from(title)),
+            ("xesam:artist", Value::from(vec!["vibeband"])),
+        ];
+        values
+            .into_iter()
+            .map(|(key, value)| {
+                let value = OwnedValue::try_from(value).map_err(zbus::Error::from)?;
+                Ok((key.to_string(), value))
+            })
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
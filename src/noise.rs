@@ -0,0 +1,245 @@
+use crate::loudness;
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+/// Length of noise rendered to measure its loudness.
+const LOUDNESS_SAMPLE: Duration = Duration::from_secs(5);
+
+/// Built-in procedural sounds, played as `noise:<kind>` scenes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoiseKind {
+    White,
+    Pink,
+    Brown,
+    Rain,
+    Drone,
+}
+
+pub const NOISE_KINDS: &[NoiseKind] = &[
+    NoiseKind::White,
+    NoiseKind::Pink,
+    NoiseKind::Brown,
+    NoiseKind::Rain,
+    NoiseKind::Drone,
+];
+
+impl NoiseKind {
+    /// Parse a scene name like `noise:brown`.
+    pub fn from_scene(scene: &str) -> Option<Self> {
+        let kind = scene.strip_prefix("noise:")?;
+        NOISE_KINDS.iter().copied().find(|k| k.name() == kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NoiseKind::White => "white",
+            NoiseKind::Pink => "pink",
+            NoiseKind::Brown => "brown",
+            NoiseKind::Rain => "rain",
+            NoiseKind::Drone => "drone",
+        }
+    }
+
+    pub fn emoji(self) -> &'static str {
+        match self {
+            NoiseKind::White | NoiseKind::Pink | NoiseKind::Brown => "📻",
+            NoiseKind::Rain => "🌧",
+            NoiseKind::Drone => "🎛",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NoiseKind::White => "White Noise",
+            NoiseKind::Pink => "Pink Noise",
+            NoiseKind::Brown => "Brown Noise",
+            NoiseKind::Rain => "Synth Rain",
+            NoiseKind::Drone => "Drone",
+        }
+    }
+
+    /// Integrated loudness in LUFS, measured over a few seconds of output.
+    pub fn loudness(self) -> Option<f32> {
+        loudness::integrated(NoiseSource::new(self).take_duration(LOUDNESS_SAMPLE))
+    }
+}
+
+/// Small xorshift PRNG — plenty for noise and keeps us dependency-free.
+struct Rng(u32);
+
+impl Rng {
+    fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    /// Uniform sample in -1.0..1.0
+    fn bipolar(&mut self) -> f32 {
+        (self.next_u32() as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    /// Uniform sample in 0.0..1.0
+    fn unit(&mut self) -> f32 {
+        self.next_u32() as f32 / u32::MAX as f32
+    }
+}
+
+/// Per-channel generator state.
+struct Voice {
+    rng: Rng,
+    // Pink noise (Paul Kellet's refined filter)
+    b: [f32; 7],
+    // Brown noise integrator
+    brown: f32,
+    // Rain: one-pole filters + droplet envelope
+    lp: f32,
+    hp_prev_in: f32,
+    hp_prev_out: f32,
+    drop_env: f32,
+    drop_lp: f32,
+}
+
+impl Voice {
+    fn new(seed: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            b: [0.0; 7],
+            brown: 0.0,
+            lp: 0.0,
+            hp_prev_in: 0.0,
+            hp_prev_out: 0.0,
+            drop_env: 0.0,
+            drop_lp: 0.0,
+        }
+    }
+
+    fn white(&mut self) -> f32 {
+        self.rng.bipolar()
+    }
+
+    fn pink(&mut self) -> f32 {
+        let w = self.white();
+        let b = &mut self.b;
+        b[0] = 0.99886 * b[0] + w * 0.0555179;
+        b[1] = 0.99332 * b[1] + w * 0.0750759;
+        b[2] = 0.96900 * b[2] + w * 0.153852;
+        b[3] = 0.86650 * b[3] + w * 0.3104856;
+        b[4] = 0.55000 * b[4] + w * 0.5329522;
+        b[5] = -0.7616 * b[5] - w * 0.0168980;
+        let out = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + w * 0.5362;
+        b[6] = w * 0.115926;
+        out * 0.11
+    }
+
+    fn brown(&mut self) -> f32 {
+        let w = self.white();
+        self.brown = (self.brown + w * 0.02) / 1.02;
+        self.brown * 3.5
+    }
+
+    fn rain(&mut self) -> f32 {
+        // Band-limited hiss: low-pass then high-pass white noise
+        let w = self.white();
+        self.lp += 0.35 * (w - self.lp);
+        let hp = 0.97 * (self.hp_prev_out + self.lp - self.hp_prev_in);
+        self.hp_prev_in = self.lp;
+        self.hp_prev_out = hp;
+
+        // Sparse droplets: short, bright noise bursts
+        if self.rng.unit() < 0.0006 {
+            self.drop_env = 0.4 + self.rng.unit() * 0.6;
+        }
+        self.drop_env *= 0.9985;
+        let burst = self.white() * self.drop_env;
+        self.drop_lp += 0.5 * (burst - self.drop_lp);
+
+        hp * 0.35 + (burst - self.drop_lp) * 0.25
+    }
+}
+
+/// An endless stereo noise source with independent channels.
+pub struct NoiseSource {
+    kind: NoiseKind,
+    voices: [Voice; CHANNELS as usize],
+    chan_idx: usize,
+    phase: [f32; 3],
+    lfo: f32,
+}
+
+impl NoiseSource {
+    pub fn new(kind: NoiseKind) -> Self {
+        Self {
+            kind,
+            voices: [Voice::new(0x9E37_79B9), Voice::new(0x85EB_CA6B)],
+            chan_idx: 0,
+            phase: [0.0; 3],
+            lfo: 0.0,
+        }
+    }
+
+    fn drone(&mut self, ch: usize) -> f32 {
+        // Three slightly detuned partials, slowly breathing, over a pink bed
+        const FREQS: [f32; 3] = [55.0, 82.6, 110.3];
+        if ch == 0 {
+            for (p, f) in self.phase.iter_mut().zip(FREQS) {
+                *p = (*p + f / SAMPLE_RATE as f32) % 1.0;
+            }
+            self.lfo = (self.lfo + 0.07 / SAMPLE_RATE as f32) % 1.0;
+        }
+        let swell = 0.75 + 0.25 * (self.lfo * TAU).sin();
+        let offset = if ch == 0 { 0.0 } else { 0.013 };
+        let tone: f32 = self
+            .phase
+            .iter()
+            .enumerate()
+            .map(|(i, p)| ((p + offset * (i + 1) as f32) * TAU).sin() / (i + 1) as f32)
+            .sum();
+        tone * 0.2 * swell + self.voices[ch].pink() * 0.05
+    }
+}
+
+impl Iterator for NoiseSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let ch = self.chan_idx;
+        self.chan_idx = (self.chan_idx + 1) % CHANNELS as usize;
+        let sample = match self.kind {
+            NoiseKind::White => self.voices[ch].white() * 0.3,
+            NoiseKind::Pink => self.voices[ch].pink(),
+            NoiseKind::Brown => self.voices[ch].brown(),
+            NoiseKind::Rain => self.voices[ch].rain(),
+            NoiseKind::Drone => self.drone(ch),
+        };
+        Some(sample.clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for NoiseSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
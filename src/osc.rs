@@ -0,0 +1,318 @@
+use crate::config::OscConfig;
+use crate::control::{self, Command, LayerRef, Request, Status};
+use anyhow::{bail, Context, Result};
+use futures::future::join_all;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch};
+
+/// Address the band levels go out on, one float per band, low to high.
+pub const BANDS_ADDR: &str = "/vibeband/bands";
+
+/// One OSC argument. Only the types control surfaces commonly send are
+/// understood.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+    Bool(bool),
+}
+
+impl Arg {
+    /// The argument as a number; NaN and infinities count as none.
+    fn as_f32(&self) -> Option<f32> {
+        match self {
+            Arg::Int(i) => Some(*i as f32),
+            Arg::Float(f) => Some(*f).filter(|f| f.is_finite()),
+            Arg::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Arg::Str(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub addr: String,
+    pub args: Vec<Arg>,
+}
+
+/// Decode a UDP packet: a message, or a bundle of them (flattened, with
+/// time tags ignored).
+pub fn decode(packet: &[u8]) -> Result<Vec<Message>> {
+    let mut messages = Vec::new();
+    decode_into(packet, &mut messages)?;
+    Ok(messages)
+}
+
+fn decode_into(packet: &[u8], messages: &mut Vec<Message>) -> Result<()> {
+    let mut reader = Reader { data: packet };
+    let addr = reader.string()?;
+    if addr == "#bundle" {
+        reader.take(8)?;
+        while !reader.data.is_empty() {
+            let size = reader.i32()?;
+            let Ok(size) = usize::try_from(size) else {
+                bail!("bad OSC bundle element size {}", size);
+            };
+            decode_into(reader.take(size)?, messages)?;
+        }
+        return Ok(());
+    }
+    if !addr.starts_with('/') {
+        bail!("bad OSC address '{}'", addr);
+    }
+
+    let mut args = Vec::new();
+    // Very old senders leave out the type tags, and with them the arguments
+    if !reader.data.is_empty() {
+        let tags = reader.string()?;
+        let Some(tags) = tags.strip_prefix(',') else {
+            bail!("bad OSC type tags '{}'", tags);
+        };
+        for tag in tags.chars() {
+            args.push(match tag {
+                'i' => Arg::Int(reader.i32()?),
+                'f' => Arg::Float(f32::from_bits(reader.i32()? as u32)),
+                'd' => Arg::Float(f64::from_be_bytes(reader.take(8)?.try_into()?) as f32),
+                's' | 'S' => Arg::Str(reader.string()?),
+                'T' => Arg::Bool(true),
+                'F' => Arg::Bool(false),
+                _ => bail!("unsupported OSC type tag '{}'", tag),
+            });
+        }
+    }
+    messages.push(Message { addr, args });
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.data.len() {
+            bail!("truncated OSC packet");
+        }
+        let (head, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// A NUL-terminated string, padded to a multiple of four bytes.
+    fn string(&mut self) -> Result<String> {
+        let len = self
+            .data
+            .iter()
+            .position(|&b| b == 0)
+            .context("truncated OSC packet")?;
+        let s = std::str::from_utf8(&self.data[..len])
+            .context("OSC string is not UTF-8")?
+            .to_string();
+        self.take(padded(len + 1).min(self.data.len()))?;
+        Ok(s)
+    }
+}
+
+fn padded(len: usize) -> usize {
+    len.div_ceil(4) * 4
+}
+
+/// Encode a single message.
+pub fn encode(message: &Message) -> Vec<u8> {
+    fn push_str(out: &mut Vec<u8>, s: &str) {
+        out.extend_from_slice(s.as_bytes());
+        out.resize(out.len() + padded(s.len() + 1) - s.len(), 0);
+    }
+
+    let mut out = Vec::new();
+    push_str(&mut out, &message.addr);
+    let tags: String = message
+        .args
+        .iter()
+        .map(|arg| match arg {
+            Arg::Int(_) => 'i',
+            Arg::Float(_) => 'f',
+            Arg::Str(_) => 's',
+            Arg::Bool(true) => 'T',
+            Arg::Bool(false) => 'F',
+        })
+        .collect();
+    push_str(&mut out, &format!(",{}", tags));
+    for arg in &message.args {
+        match arg {
+            Arg::Int(i) => out.extend_from_slice(&i.to_be_bytes()),
+            Arg::Float(f) => out.extend_from_slice(&f.to_be_bytes()),
+            Arg::Str(s) => push_str(&mut out, s),
+            Arg::Bool(_) => {}
+        }
+    }
+    out
+}
+
+/// The command for a message, given what is playing. `None` when there's
+/// nothing to do, e.g. muting a layer that already is.
+///
+/// - `/vibeband/master <level>`
+/// - `/vibeband/pause [on]`: toggles without an argument
+/// - `/vibeband/layer/<n>/volume <level>`
+/// - `/vibeband/layer/<n>/mute [on]` and `/vibeband/layer/<n>/solo [on]`
+///
+/// `<n>` is a layer position from 1 or a scene name; levels run 0 to 1.
+pub fn command(message: &Message, status: &Status) -> Result<Option<Command>> {
+    let path = message.addr.strip_prefix("/vibeband/").unwrap_or("");
+    let parts: Vec<&str> = path.split('/').collect();
+    let arg = message.args.first();
+    let level = || -> Result<f32> {
+        match arg.and_then(Arg::as_f32) {
+            Some(level) => Ok(level.clamp(0.0, 1.0)),
+            None => bail!("{} needs a level from 0 to 1", message.addr),
+        }
+    };
+    // A switch sets its state when given an argument and toggles without
+    let switch = |on: bool| match arg.and_then(Arg::as_f32) {
+        Some(value) => (value >= 0.5) != on,
+        None => true,
+    };
+
+    let command = match parts.as_slice() {
+        ["master"] => Command::Master { volume: level()? },
+        ["pause"] => match arg.and_then(Arg::as_f32) {
+            Some(value) if value >= 0.5 => Command::Pause,
+            Some(_) => Command::Play,
+            None => Command::Toggle,
+        },
+        ["layer", n, control] => {
+            let Some(layer) = status.layer(n) else {
+                bail!("No layer '{}' is playing", n);
+            };
+            let name = LayerRef::Name(layer.name.clone());
+            match *control {
+                "volume" => Command::Volume {
+                    layer: name,
+                    volume: level()?,
+                },
+                "mute" if switch(layer.muted) => Command::Mute { layer: name },
+                "solo" if switch(layer.soloed) => Command::Solo { layer: name },
+                "mute" | "solo" => return Ok(None),
+                _ => bail!("unknown OSC address {}", message.addr),
+            }
+        }
+        _ => bail!("unknown OSC address {}", message.addr),
+    };
+    Ok(Some(command))
+}
+
+/// Drop messages that a later one to the same address overrides. Messages
+/// without arguments toggle, so they all count.
+pub fn coalesce(messages: Vec<Message>) -> Vec<Message> {
+    let mut kept: Vec<Message> = Vec::with_capacity(messages.len());
+    for message in messages {
+        if !message.args.is_empty() {
+            kept.retain(|m| m.args.is_empty() || m.addr != message.addr);
+        }
+        kept.push(message);
+    }
+    kept
+}
+
+/// Carry out `messages` against one status. The commands go out together,
+/// so the player handles them all in the same tick. Fails once the player
+/// has stopped.
+async fn apply(tx: &mpsc::UnboundedSender<Request>, messages: Vec<Message>) -> Result<()> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let Some(status) = control::request(tx, Command::Status).await?.status else {
+        return Ok(());
+    };
+    let commands = messages
+        .iter()
+        .filter_map(|message| command(message, &status).ok().flatten());
+    for reply in join_all(commands.map(|command| control::request(tx, command))).await {
+        reply?;
+    }
+    Ok(())
+}
+
+/// Background tasks for the OSC listener and feedback; stopped when
+/// dropped.
+pub struct Osc {
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl Drop for Osc {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Listen for OSC commands and pass them on to `tx`, and send band levels
+/// from `updates` to the feedback address. `None` when neither is
+/// configured.
+pub async fn serve(
+    config: &OscConfig,
+    tx: mpsc::UnboundedSender<Request>,
+    mut updates: watch::Receiver<Status>,
+) -> Result<Option<Osc>> {
+    if config.listen.is_none() && config.feedback.is_none() {
+        return Ok(None);
+    }
+    // Feedback goes out from the listening port, as control surfaces expect
+    let bind = config.listen.as_deref().unwrap_or("0.0.0.0:0");
+    let socket = UdpSocket::bind(bind)
+        .await
+        .with_context(|| format!("failed to listen for OSC on {}", bind))?;
+    let socket = std::sync::Arc::new(socket);
+    let mut tasks = Vec::new();
+
+    if config.listen.is_some() {
+        let socket = socket.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut buf = vec![0; 65536];
+            while let Ok((len, _)) = socket.recv_from(&mut buf).await {
+                // UDP has no way to answer, so bad messages are dropped
+                let mut messages = decode(&buf[..len]).unwrap_or_default();
+                // A fader sweep sends far more than the player takes in a
+                // tick, so take all that is waiting and keep the latest
+                while let Ok((len, _)) = socket.try_recv_from(&mut buf) {
+                    messages.extend(decode(&buf[..len]).unwrap_or_default());
+                }
+                if apply(&tx, coalesce(messages)).await.is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    if let Some(feedback) = &config.feedback {
+        let target = tokio::net::lookup_host(feedback.as_str())
+            .await
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .with_context(|| format!("bad OSC feedback address {}", feedback))?;
+        tasks.push(tokio::spawn(async move {
+            while updates.changed().await.is_ok() {
+                let args = updates
+                    .borrow_and_update()
+                    .spectrum
+                    .iter()
+                    .map(|&level| Arg::Float(level as f32))
+                    .collect();
+                let message = Message {
+                    addr: BANDS_ADDR.to_string(),
+                    args,
+                };
+                let _ = socket.send_to(&encode(&message), target).await;
+            }
+        }));
+    }
+
+    Ok(Some(Osc { tasks }))
+}
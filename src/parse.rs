@@ -0,0 +1,80 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+/// Longest span `parse_span` accepts.
+const MAX_SPAN: Duration = Duration::from_secs(365 * 86400);
+
+/// Parse a time span like `90s`, `45m`, `2h` or `30d`, up to a year.
+pub fn parse_span(s: &str) -> std::result::Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let value: f64 = num
+        .parse()
+        .map_err(|_| format!("invalid time span '{s}' (try 90s, 45m, 2h or 30d)"))?;
+    let secs = match unit {
+        "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        "d" => value * 86400.0,
+        _ => return Err(format!("invalid time span '{s}' (try 90s, 45m, 2h or 30d)")),
+    };
+    let span = Duration::try_from_secs_f64(secs)
+        .map_err(|_| format!("invalid time span '{s}' (try 90s, 45m, 2h or 30d)"))?;
+    if span > MAX_SPAN {
+        return Err(format!("time span '{s}' is too long (at most 365d)"));
+    }
+    Ok(span)
+}
+
+/// Parse a span range like `20-90s` or `30s-2m`. A bare lower bound takes
+/// the upper bound's unit.
+pub fn parse_span_range(s: &str) -> std::result::Result<(Duration, Duration), String> {
+    let (min, max) = s
+        .split_once('-')
+        .ok_or_else(|| format!("invalid range '{s}' (try 20-90s or 30s-2m)"))?;
+    let max_span = parse_span(max)?;
+    let min_span = if min.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        let unit = max.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        parse_span(&format!("{min}{unit}"))?
+    } else {
+        parse_span(min)?
+    };
+    Ok((min_span, max_span))
+}
+
+/// Split a mix layer like `rain@40` into the scene and its volume, if
+/// given as a percentage.
+pub fn parse_mix_layer(arg: &str) -> Result<(&str, Option<f32>)> {
+    let Some((scene, pct)) = arg.rsplit_once('@') else {
+        return Ok((arg, None));
+    };
+    let Some(pct) = pct
+        .trim_end_matches('%')
+        .parse::<f32>()
+        .ok()
+        .filter(|pct| pct.is_finite())
+    else {
+        bail!("Bad volume in '{}': use e.g. rain@40", arg);
+    };
+    Ok((scene, Some((pct / 100.0).clamp(0.0, 1.0))))
+}
+
+/// A volume from a percentage: `30` sets it, `+10` and `-10` step from
+/// `current`.
+pub fn parse_level(arg: &str, current: f32) -> Result<f32> {
+    let Some(pct) = arg.parse::<f32>().ok().filter(|pct| pct.is_finite()) else {
+        bail!(
+            "Bad volume '{}': use a percentage such as 30, +10 or -10",
+            arg
+        );
+    };
+    let volume = if arg.starts_with(['+', '-']) {
+        current + pct / 100.0
+    } else {
+        pct / 100.0
+    };
+    Ok(((volume * 100.0).round() / 100.0).clamp(0.0, 1.0))
+}
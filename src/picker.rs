@@ -0,0 +1,119 @@
+use crate::noise::NOISE_KINDS;
+use crate::presets::{EVENT_PRESETS, PRESETS};
+use crate::user_presets;
+
+/// A scene that can be added from the in-player picker.
+pub struct PickerItem {
+    pub name: String,
+    pub emoji: String,
+    pub label: String,
+}
+
+/// Fuzzy-searchable list of every playable scene: built-in presets, random
+/// events, offline noise and user presets.
+pub struct Picker {
+    pub query: String,
+    pub selected: usize,
+    items: Vec<PickerItem>,
+}
+
+impl Picker {
+    pub fn new() -> Self {
+        let mut items: Vec<PickerItem> = PRESETS
+            .iter()
+            .map(|p| PickerItem {
+                name: p.name.to_string(),
+                emoji: p.emoji.to_string(),
+                label: p.label.to_string(),
+            })
+            .collect();
+        for p in EVENT_PRESETS {
+            items.push(PickerItem {
+                name: p.name.to_string(),
+                emoji: p.emoji.to_string(),
+                label: p.label.to_string(),
+            });
+        }
+        for kind in NOISE_KINDS {
+            items.push(PickerItem {
+                name: format!("noise:{}", kind.name()),
+                emoji: kind.emoji().to_string(),
+                label: kind.label().to_string(),
+            });
+        }
+        if let Ok(user) = user_presets::load() {
+            for (name, p) in user.presets {
+                items.push(PickerItem {
+                    name,
+                    emoji: p.emoji,
+                    label: p.label,
+                });
+            }
+        }
+        Self {
+            query: String::new(),
+            selected: 0,
+            items,
+        }
+    }
+
+    /// Items matching the query, best match first.
+    pub fn matches(&self) -> Vec<&PickerItem> {
+        let mut scored: Vec<(i32, usize, &PickerItem)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                let name = fuzzy_score(&self.query, &item.name);
+                let label = fuzzy_score(&self.query, &item.label);
+                name.max(label).map(|score| (score, i, item))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, i, _)| (std::cmp::Reverse(score), i));
+        scored.into_iter().map(|(_, _, item)| item).collect()
+    }
+
+    pub fn current(&self) -> Option<&PickerItem> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn move_selection(&mut self, delta: isize) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(count as isize) as usize;
+        }
+    }
+}
+
+/// Score `text` against `query` as a case-insensitive subsequence, or `None`
+/// if some query character is missing. Consecutive matches and matches at
+/// word starts score higher.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = (pos..text.len()).find(|&i| text[i] == q)?;
+        score += 1;
+        if prev.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        prev = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
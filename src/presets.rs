@@ -1,3 +1,5 @@
+use crate::events::EventSpec;
+
 pub struct Preset {
     pub name: &'static str,
     pub emoji: &'static str,
@@ -116,3 +118,81 @@ pub const PRESETS: &[Preset] = &[
 pub fn find_preset(name: &str) -> Option<&'static Preset> {
     PRESETS.iter().find(|p| p.name == name)
 }
+
+/// A preset of short one-shots scattered at random over the other layers.
+pub struct EventPreset {
+    pub name: &'static str,
+    pub emoji: &'static str,
+    pub label: &'static str,
+    pub prompt: &'static str,
+    /// Length of each generated one-shot.
+    pub duration_seconds: f32,
+    pub spec: EventSpec,
+}
+
+pub const EVENT_PRESETS: &[EventPreset] = &[
+    EventPreset {
+        name: "thunderclap",
+        emoji: "⚡",
+        label: "Thunderclaps",
+        prompt: "A single loud thunderclap followed by a long rolling rumble fading away",
+        duration_seconds: 8.0,
+        spec: EventSpec {
+            min_gap_secs: 40.0,
+            max_gap_secs: 180.0,
+            min_gain: 0.4,
+            max_gain: 1.0,
+            pan: 0.6,
+            variations: 3,
+        },
+    },
+    EventPreset {
+        name: "birdcall",
+        emoji: "🐤",
+        label: "Bird Calls",
+        prompt: "A single songbird call, short and clear, outdoors with no background noise",
+        duration_seconds: 3.0,
+        spec: EventSpec {
+            min_gap_secs: 6.0,
+            max_gap_secs: 30.0,
+            min_gain: 0.3,
+            max_gain: 0.9,
+            pan: 1.0,
+            variations: 4,
+        },
+    },
+    EventPreset {
+        name: "chime",
+        emoji: "🎐",
+        label: "Door Chimes",
+        prompt: "A shop door opening with a small bell chime ringing once",
+        duration_seconds: 3.0,
+        spec: EventSpec {
+            min_gap_secs: 60.0,
+            max_gap_secs: 240.0,
+            min_gain: 0.5,
+            max_gain: 0.9,
+            pan: 0.5,
+            variations: 2,
+        },
+    },
+    EventPreset {
+        name: "owl",
+        emoji: "🦉",
+        label: "Owl Hoots",
+        prompt: "A distant owl hooting twice at night in a quiet forest",
+        duration_seconds: 4.0,
+        spec: EventSpec {
+            min_gap_secs: 20.0,
+            max_gap_secs: 90.0,
+            min_gain: 0.3,
+            max_gain: 0.8,
+            pan: 0.9,
+            variations: 3,
+        },
+    },
+];
+
+pub fn find_event_preset(name: &str) -> Option<&'static EventPreset> {
+    EVENT_PRESETS.iter().find(|p| p.name == name)
+}
@@ -0,0 +1,152 @@
+use rodio::Source;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Target gain for a `RampedSource`, set from the UI thread.
+pub struct Gain {
+    target: AtomicU32,
+    ramp_ms: AtomicU32,
+    /// Bumped on every change so the source can restart its ramp.
+    generation: AtomicU32,
+    paused: AtomicBool,
+    stopped: AtomicBool,
+}
+
+pub type SharedGain = Arc<Gain>;
+
+impl Gain {
+    /// A gain that starts silent.
+    pub fn new() -> SharedGain {
+        Arc::new(Self {
+            target: AtomicU32::new(0.0f32.to_bits()),
+            ramp_ms: AtomicU32::new(0),
+            generation: AtomicU32::new(0),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    /// Move linearly from the current gain to `target` over `over`.
+    pub fn ramp_to(&self, target: f32, over: Duration) {
+        self.ramp_ms.store(
+            over.as_millis().min(u32::MAX as u128) as u32,
+            Ordering::Relaxed,
+        );
+        self.target.store(target.to_bits(), Ordering::Relaxed);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// While paused, the source stops pulling audio once it has ramped to
+    /// silence, so playback resumes where it left off.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// End the source, dropping it from the mix.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// A Source wrapper that applies a smoothly ramped gain.
+pub struct RampedSource<S> {
+    inner: S,
+    gain: SharedGain,
+    channels: u16,
+    chan_idx: u16,
+    sample_rate: u32,
+    generation: u32,
+    current: f32,
+    target: f32,
+    /// Gain change per frame while ramping.
+    step: f32,
+    frozen: bool,
+}
+
+impl<S> RampedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, gain: SharedGain) -> Self {
+        let channels = inner.channels().max(1);
+        let sample_rate = inner.sample_rate();
+        Self {
+            inner,
+            gain,
+            channels,
+            chan_idx: 0,
+            sample_rate,
+            generation: u32::MAX,
+            current: 0.0,
+            target: 0.0,
+            step: 0.0,
+            frozen: false,
+        }
+    }
+
+    /// Advance the gain by one frame.
+    fn tick(&mut self) {
+        let generation = self.gain.generation.load(Ordering::Acquire);
+        if generation != self.generation {
+            self.generation = generation;
+            self.target = f32::from_bits(self.gain.target.load(Ordering::Relaxed));
+            let ramp_ms = self.gain.ramp_ms.load(Ordering::Relaxed);
+            let frames = ramp_ms as f32 * self.sample_rate as f32 / 1000.0;
+            self.step = if frames < 1.0 {
+                f32::INFINITY
+            } else {
+                (self.target - self.current).abs() / frames
+            };
+        }
+
+        if self.current < self.target {
+            self.current = (self.current + self.step).min(self.target);
+        } else if self.current > self.target {
+            self.current = (self.current - self.step).max(self.target);
+        }
+        self.frozen = self.current == 0.0 && self.gain.paused.load(Ordering::Relaxed);
+    }
+}
+
+impl<S> Iterator for RampedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.chan_idx == 0 {
+            if self.gain.stopped.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.tick();
+        }
+        self.chan_idx = (self.chan_idx + 1) % self.channels;
+        if self.frozen {
+            return Some(0.0);
+        }
+        Some(self.inner.next()? * self.current)
+    }
+}
+
+impl<S> Source for RampedSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
@@ -0,0 +1,153 @@
+use crate::cache;
+use crate::generator::SoundRequest;
+use anyhow::{bail, Context, Result};
+use rodio::{Decoder, Source};
+use std::f32::consts::FRAC_PI_2;
+use std::io::Cursor;
+use std::time::Duration;
+
+/// How far back from the end of a clip to look for the loop point.
+const SEARCH_SECS: f32 = 5.0;
+/// Upper bound on points compared per candidate loop point.
+const MAX_COMPARE: usize = 2048;
+/// Correlation given up for cutting the whole search window off the clip.
+const LENGTH_BIAS: f32 = 0.1;
+
+/// Interleaved decoded audio.
+struct Pcm {
+    channels: usize,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl Pcm {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Mono mix of one frame.
+    fn mono(&self, frame: usize) -> f32 {
+        let start = frame * self.channels;
+        self.samples[start..start + self.channels]
+            .iter()
+            .sum::<f32>()
+            / self.channels as f32
+    }
+}
+
+/// The clip a layer should loop: `data` with its seam crossfaded, read from
+/// the cache when this take has been processed before. A clip that isn't
+/// cached (no `take`) is processed every time. Falls back to `data`
+/// unchanged if the clip can't be processed.
+pub fn seamless(
+    request: &SoundRequest,
+    take: Option<u32>,
+    data: Vec<u8>,
+    crossfade: Duration,
+) -> Vec<u8> {
+    let key = take.map(|take| cache::take_key(&cache::cache_key(request), take));
+    let ms = crossfade.as_millis() as u64;
+    if let Some(key) = &key {
+        if let Ok(Some(processed)) = cache::read_seam(key, ms) {
+            return processed;
+        }
+    }
+    match crossfade_loop(&data, crossfade) {
+        Ok(processed) => {
+            if let Some(key) = &key {
+                let _ = cache::write_seam(key, ms, &processed);
+            }
+            processed
+        }
+        Err(_) => data,
+    }
+}
+
+/// Decode a clip, find the loop point whose lead-in best matches the start
+/// of the clip, and equal-power crossfade the tail into the head. Returns a
+/// WAV that loops without a click.
+pub fn crossfade_loop(data: &[u8], crossfade: Duration) -> Result<Vec<u8>> {
+    let pcm = decode(data)?;
+    let frames = pcm.frames();
+    let fade = ((crossfade.as_secs_f32() * pcm.sample_rate as f32) as usize).min(frames / 4);
+    if fade < 64 {
+        bail!("clip too short to crossfade");
+    }
+
+    let end = find_loop_end(&pcm, fade);
+    let ch = pcm.channels;
+    let mut out = pcm.samples[fade * ch..end * ch].to_vec();
+    // The last `fade` frames blend into the first `fade`, which the loop
+    // skips, so the wrap lands exactly where the head left off
+    let tail = out.len() - fade * ch;
+    for i in 0..fade {
+        let theta = (i as f32 + 0.5) / fade as f32 * FRAC_PI_2;
+        let (fade_in, fade_out) = theta.sin_cos();
+        for c in 0..ch {
+            let s = &mut out[tail + i * ch + c];
+            *s = *s * fade_out + pcm.samples[i * ch + c] * fade_in;
+        }
+    }
+    encode_wav(&out, pcm.channels as u16, pcm.sample_rate)
+}
+
+fn decode(data: &[u8]) -> Result<Pcm> {
+    let decoder = Decoder::new(Cursor::new(data.to_vec())).context("failed to decode audio")?;
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate();
+    let samples: Vec<f32> = decoder.convert_samples().collect();
+    Ok(Pcm {
+        channels,
+        sample_rate,
+        samples,
+    })
+}
+
+/// Pick the frame to end the loop on: the candidate near the end of the clip
+/// whose `fade` frames before it correlate best with the clip's first
+/// `fade` frames.
+fn find_loop_end(pcm: &Pcm, fade: usize) -> usize {
+    let frames = pcm.frames();
+    let search = ((SEARCH_SECS * pcm.sample_rate as f32) as usize).min(frames / 4);
+    let first = (frames - search).max(2 * fade);
+    let stride = (fade / MAX_COMPARE).max(1);
+    // About one candidate per millisecond
+    let hop = (pcm.sample_rate as usize / 1000).max(1);
+
+    let head: Vec<f32> = (0..fade).step_by(stride).map(|i| pcm.mono(i)).collect();
+    let head_energy: f32 = head.iter().map(|s| s * s).sum();
+
+    let mut best = (f32::MIN, frames);
+    for end in (first..=frames).rev().step_by(hop) {
+        let start = end - fade;
+        let (mut dot, mut energy) = (0.0, 0.0);
+        for (k, h) in head.iter().enumerate() {
+            let t = pcm.mono(start + k * stride);
+            dot += t * h;
+            energy += t * t;
+        }
+        let correlation = dot / (energy * head_energy).sqrt().max(f32::EPSILON);
+        // Near-ties go to the longer loop, keeping more of the clip
+        let score = correlation - LENGTH_BIAS * (frames - end) as f32 / search.max(1) as f32;
+        if score > best.0 {
+            best = (score, end);
+        }
+    }
+    best.1
+}
+
+fn encode_wav(samples: &[f32], channels: u16, sample_rate: u32) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut out, spec)?;
+    for &s in samples {
+        writer.write_sample((s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(out.into_inner())
+}
@@ -0,0 +1,224 @@
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Per-frame smoothing of gain changes, roughly 20ms at 44.1kHz.
+const SMOOTHING: f32 = 0.001;
+
+/// Where a layer sits in the stereo field.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stereo {
+    /// -1 (hard left) to 1 (hard right).
+    #[serde(skip_serializing_if = "is_centred")]
+    pub pan: f32,
+    /// 0 folds the layer to mono, 1 keeps its original stereo image.
+    #[serde(skip_serializing_if = "is_full_width")]
+    pub width: f32,
+}
+
+fn is_centred(pan: &f32) -> bool {
+    *pan == 0.0
+}
+
+fn is_full_width(width: &f32) -> bool {
+    *width == 1.0
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            pan: 0.0,
+            width: 1.0,
+        }
+    }
+}
+
+impl Stereo {
+    pub fn clamped(self) -> Self {
+        Self {
+            pan: self.pan.clamp(-1.0, 1.0),
+            width: self.width.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Short label such as `L40 W60`, empty at the defaults.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        let pan = (self.pan * 100.0).round() as i32;
+        if pan < 0 {
+            parts.push(format!("L{}", -pan));
+        } else if pan > 0 {
+            parts.push(format!("R{}", pan));
+        }
+        if self.width < 1.0 {
+            parts.push(format!("W{}", (self.width * 100.0).round()));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Stereo placement for a `StereoSource`, set from the UI thread.
+pub struct StereoControl {
+    pan: AtomicU32,
+    width: AtomicU32,
+}
+
+pub type SharedStereo = Arc<StereoControl>;
+
+impl StereoControl {
+    pub fn new(stereo: Stereo) -> SharedStereo {
+        let control = Arc::new(Self {
+            pan: AtomicU32::new(0),
+            width: AtomicU32::new(0),
+        });
+        control.set(stereo);
+        control
+    }
+
+    pub fn set(&self, stereo: Stereo) {
+        let stereo = stereo.clamped();
+        self.pan.store(stereo.pan.to_bits(), Ordering::Relaxed);
+        self.width.store(stereo.width.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Stereo {
+        Stereo {
+            pan: f32::from_bits(self.pan.load(Ordering::Relaxed)),
+            width: f32::from_bits(self.width.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Constant-power (left, right) gains for a point at `pos`, -1 to 1.
+fn pan_gains(pos: f32) -> [f32; 2] {
+    let angle = (pos.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    [angle.cos(), angle.sin()]
+}
+
+/// Linear (left, right) gains for a point at `pos`, -1 to 1.
+fn balance_gains(pos: f32) -> [f32; 2] {
+    let pos = pos.clamp(-1.0, 1.0);
+    [(1.0 - pos) / 2.0, (1.0 + pos) / 2.0]
+}
+
+/// A Source wrapper that places any input in the stereo field and always
+/// outputs two channels.
+///
+/// Mono input is panned as a point source with a constant-power law,
+/// scaled so that at centre it plays at full level in both channels, as it
+/// did before it could be panned. Stereo input keeps its left and right channels as two points
+/// `width` either side of `pan`, placed with a linear law so that narrowing
+/// to mono leaves the mid signal at its original level. Extra channels
+/// beyond the first two are dropped.
+pub struct StereoSource<S> {
+    inner: S,
+    control: SharedStereo,
+    channels: u16,
+    /// Output frame being played out.
+    frame: [f32; 2],
+    out_idx: usize,
+    placed: Stereo,
+    /// Smoothed `[from left, from right]` gains into each output channel.
+    gains: [[f32; 2]; 2],
+    target: [[f32; 2]; 2],
+}
+
+impl<S> StereoSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, control: SharedStereo) -> Self {
+        let channels = inner.channels().max(1);
+        let placed = control.get();
+        let target = Self::gains_for(channels, placed);
+        Self {
+            inner,
+            control,
+            channels,
+            frame: [0.0; 2],
+            out_idx: 0,
+            placed,
+            gains: target,
+            target,
+        }
+    }
+
+    /// Output gains, indexed `[output channel][input channel]`.
+    fn gains_for(channels: u16, stereo: Stereo) -> [[f32; 2]; 2] {
+        if channels == 1 {
+            let [l, r] = pan_gains(stereo.pan).map(|g| g * SQRT_2);
+            return [[l, 0.0], [r, 0.0]];
+        }
+        let left = balance_gains(stereo.pan - stereo.width);
+        let right = balance_gains(stereo.pan + stereo.width);
+        [[left[0], right[0]], [left[1], right[1]]]
+    }
+
+    /// Pull one input frame and mix it into the next output frame.
+    fn next_frame(&mut self) -> Option<()> {
+        let placed = self.control.get();
+        if placed != self.placed {
+            self.placed = placed;
+            self.target = Self::gains_for(self.channels, placed);
+        }
+
+        let mut input = [0.0; 2];
+        for c in 0..self.channels as usize {
+            let sample = self.inner.next()?;
+            if let Some(slot) = input.get_mut(c) {
+                *slot = sample;
+            }
+        }
+        for out in 0..2 {
+            let mut sample = 0.0;
+            for (c, &x) in input.iter().enumerate() {
+                let g = &mut self.gains[out][c];
+                *g += (self.target[out][c] - *g) * SMOOTHING;
+                sample += x * *g;
+            }
+            self.frame[out] = sample;
+        }
+        Some(())
+    }
+}
+
+impl<S> Iterator for StereoSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.out_idx == 0 {
+            self.next_frame()?;
+        }
+        let sample = self.frame[self.out_idx];
+        self.out_idx = (self.out_idx + 1) % 2;
+        Some(sample)
+    }
+}
+
+impl<S> Source for StereoSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
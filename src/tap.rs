@@ -26,8 +26,8 @@ impl RingBuffer {
         let n = n.min(RING_SIZE);
         let mut out = vec![0.0; n];
         let start = (self.pos + RING_SIZE - n) % RING_SIZE;
-        for i in 0..n {
-            out[i] = self.buf[(start + i) % RING_SIZE];
+        for (i, s) in out.iter_mut().enumerate() {
+            *s = self.buf[(start + i) % RING_SIZE];
         }
         out
     }
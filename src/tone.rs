@@ -0,0 +1,275 @@
+use anyhow::{bail, Result};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, SQRT_2};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Largest cut or boost any band allows, in dB.
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+/// Corner of the low shelf.
+const LOW_HZ: f32 = 200.0;
+/// Centre of the mid band.
+const MID_HZ: f32 = 1000.0;
+const MID_Q: f32 = 0.7;
+/// Corner of the high shelf.
+const HIGH_HZ: f32 = 5000.0;
+
+/// Three-band EQ settings: a low shelf, a mid peak and a high shelf.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tone {
+    /// Gain below about 200Hz, in dB. Cut to tame boomy sounds.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub low: f32,
+    /// Gain around 1kHz, in dB.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub mid: f32,
+    /// Gain above about 5kHz, in dB. Cut to tame hiss.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub high: f32,
+}
+
+fn is_zero(db: &f32) -> bool {
+    *db == 0.0
+}
+
+impl Tone {
+    pub fn is_flat(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn clamped(self) -> Self {
+        Self {
+            low: self.low.clamp(-MAX_GAIN_DB, MAX_GAIN_DB),
+            mid: self.mid.clamp(-MAX_GAIN_DB, MAX_GAIN_DB),
+            high: self.high.clamp(-MAX_GAIN_DB, MAX_GAIN_DB),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.clamped() != *self {
+            bail!("EQ gains must be between -{0} and {0} dB", MAX_GAIN_DB);
+        }
+        Ok(())
+    }
+
+    /// Short label such as `low -3dB, high -6dB`, empty when flat.
+    pub fn describe(&self) -> String {
+        [("low", self.low), ("mid", self.mid), ("high", self.high)]
+            .iter()
+            .filter(|(_, db)| *db != 0.0)
+            .map(|(band, db)| format!("{} {:+}dB", band, db))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// EQ settings for a `ToneSource`, set from the UI thread.
+pub struct ToneControl {
+    low: AtomicU32,
+    mid: AtomicU32,
+    high: AtomicU32,
+}
+
+pub type SharedTone = Arc<ToneControl>;
+
+impl ToneControl {
+    pub fn new(tone: Tone) -> SharedTone {
+        let control = Arc::new(Self {
+            low: AtomicU32::new(0),
+            mid: AtomicU32::new(0),
+            high: AtomicU32::new(0),
+        });
+        control.set(tone);
+        control
+    }
+
+    pub fn set(&self, tone: Tone) {
+        let tone = tone.clamped();
+        self.low.store(tone.low.to_bits(), Ordering::Relaxed);
+        self.mid.store(tone.mid.to_bits(), Ordering::Relaxed);
+        self.high.store(tone.high.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Tone {
+        Tone {
+            low: f32::from_bits(self.low.load(Ordering::Relaxed)),
+            mid: f32::from_bits(self.mid.load(Ordering::Relaxed)),
+            high: f32::from_bits(self.high.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// Normalized biquad coefficients, `a0` divided out.
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+}
+
+impl Biquad {
+    fn normalized(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+        }
+    }
+
+    // Shelf and peak filters from the RBJ Audio EQ Cookbook, shelf slope 1
+
+    fn low_shelf(hz: f32, db: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(db / 40.0);
+        let w0 = 2.0 * PI * hz / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let k = 2.0 * a.sqrt() * sin / SQRT_2;
+        Self::normalized(
+            [
+                a * ((a + 1.0) - (a - 1.0) * cos + k),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - k),
+            ],
+            [
+                (a + 1.0) + (a - 1.0) * cos + k,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - k,
+            ],
+        )
+    }
+
+    fn high_shelf(hz: f32, db: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(db / 40.0);
+        let w0 = 2.0 * PI * hz / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let k = 2.0 * a.sqrt() * sin / SQRT_2;
+        Self::normalized(
+            [
+                a * ((a + 1.0) + (a - 1.0) * cos + k),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - k),
+            ],
+            [
+                (a + 1.0) - (a - 1.0) * cos + k,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - k,
+            ],
+        )
+    }
+
+    fn peak(hz: f32, q: f32, db: f32, sample_rate: f32) -> Self {
+        let a = 10f32.powf(db / 40.0);
+        let w0 = 2.0 * PI * hz / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(
+            [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+            [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+        )
+    }
+
+    /// Filter one sample, transposed direct form II.
+    fn process(&self, x: f32, state: &mut [f32; 2]) -> f32 {
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// A Source wrapper that runs every channel through a low shelf, a mid
+/// peak and a high shelf. Flat bands pass audio through untouched.
+pub struct ToneSource<S> {
+    inner: S,
+    control: SharedTone,
+    channels: usize,
+    chan_idx: usize,
+    sample_rate: u32,
+    shaped: Tone,
+    bands: [Biquad; 3],
+    /// Filter state, `[channel][band]`.
+    state: Vec<[[f32; 2]; 3]>,
+}
+
+impl<S> ToneSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, control: SharedTone) -> Self {
+        let channels = inner.channels().max(1) as usize;
+        let sample_rate = inner.sample_rate();
+        let shaped = control.get();
+        Self {
+            inner,
+            control,
+            channels,
+            chan_idx: 0,
+            sample_rate,
+            shaped,
+            bands: Self::bands_for(shaped, sample_rate),
+            state: vec![[[0.0; 2]; 3]; channels],
+        }
+    }
+
+    fn bands_for(tone: Tone, sample_rate: u32) -> [Biquad; 3] {
+        let rate = sample_rate as f32;
+        [
+            Biquad::low_shelf(LOW_HZ, tone.low, rate),
+            Biquad::peak(MID_HZ, MID_Q, tone.mid, rate),
+            Biquad::high_shelf(HIGH_HZ.min(rate * 0.45), tone.high, rate),
+        ]
+    }
+}
+
+impl<S> Iterator for ToneSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let ch = self.chan_idx;
+        if ch == 0 {
+            let shaped = self.control.get();
+            let sample_rate = self.inner.sample_rate();
+            if shaped != self.shaped || sample_rate != self.sample_rate {
+                self.shaped = shaped;
+                self.sample_rate = sample_rate;
+                self.bands = Self::bands_for(shaped, sample_rate);
+            }
+        }
+        self.chan_idx = (self.chan_idx + 1) % self.channels;
+
+        let mut sample = self.inner.next()?;
+        if self.shaped.is_flat() {
+            return Some(sample);
+        }
+        let state = &mut self.state[ch];
+        for (band, s) in self.bands.iter().zip(state.iter_mut()) {
+            sample = band.process(sample, s);
+        }
+        Some(sample)
+    }
+}
+
+impl<S> Source for ToneSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+}
@@ -1,4 +1,8 @@
-use crate::app::{App, AppState};
+use crate::control::{AppState, LayerStatus, Status};
+use crate::picker::Picker;
+use crate::stereo::Stereo;
+use crate::tone::MAX_GAIN_DB;
+use crate::ui::{Control, Overlay, Ui, CONTROLS};
 use crate::visualizer::Visualizer;
 use ratatui::{
     layout::{Constraint, Layout, Rect},
@@ -7,6 +11,7 @@ use ratatui::{
     widgets::{Block, Borders, BorderType, Padding, Paragraph},
     Frame,
 };
+use std::time::Duration;
 
 const MAX_WIDTH: u16 = 80;
 const MIN_WIDTH: u16 = 40;
@@ -18,8 +23,33 @@ const COLOR_TEXT: Color = Color::White;
 const COLOR_PLAYING: Color = Color::Green;
 const COLOR_VOL_FILL: Color = Color::Green;
 const COLOR_VOL_EMPTY: Color = Color::DarkGray;
-
-pub fn draw(frame: &mut Frame, app: &App, vis: &mut Visualizer) {
+const COLOR_MUTED: Color = Color::Red;
+
+// Gain reduction below this isn't worth showing
+const MIN_REDUCTION_DB: f32 = 0.1;
+
+// Every key, for the `?` overlay; the help bar only has room for the basics
+const KEYS: &[(&str, &str)] = &[
+    ("Space", "Pause / resume"),
+    ("↑ ↓", "Selected layer volume"),
+    ("+ -", "Master volume"),
+    ("← →", "Pan selected layer"),
+    ("[ ]", "Stereo width of selected layer"),
+    ("e", "Layer panel: volume, pan, width, EQ"),
+    ("Tab", "Select next layer"),
+    ("a", "Add a layer from presets"),
+    ("c", "Add a layer from a prompt"),
+    ("x / Del", "Remove selected layer"),
+    ("r", "Re-roll selected layer"),
+    ("m", "Mute selected layer"),
+    ("o", "Solo selected layer"),
+    ("t", "Next take of selected layer"),
+    ("s", "Save what's playing as a mix"),
+    ("z", "Sleep timer: 15/30/45/60/90 min, off"),
+    ("q", "Quit"),
+];
+
+pub fn draw(frame: &mut Frame, status: &Status, ui: &Ui) {
     let area = frame.area();
 
     // Responsive width: fill terminal but clamp to MIN..MAX
@@ -41,7 +71,7 @@ pub fn draw(frame: &mut Frame, app: &App, vis: &mut Visualizer) {
     let w = inner.width as usize;
 
     // Fixed-height sections + flexible spectrum
-    let layer_count = app.engine.layers.len() as u16;
+    let layer_count = (status.layers.len() + status.pending.len()) as u16;
     let fixed_lines: u16 = 2      // title + scene
         + 1                        // blank
         + 1                        // blank after spectrum
@@ -68,25 +98,30 @@ pub fn draw(frame: &mut Frame, app: &App, vis: &mut Visualizer) {
     let chunks = Layout::vertical(constraints).split(inner);
 
     // ── Title + scene ──
-    render_header(frame, app, chunks[0], w);
+    render_header(frame, status, chunks[0], w);
 
-    // ── Spectrum ──
+    // ── Spectrum, or the open overlay ──
     let vis_h = chunks[2].height as usize;
-    let vis_lines = vis.render(w, vis_h);
-    frame.render_widget(Paragraph::new(vis_lines), chunks[2]);
+    match &ui.overlay {
+        Some(overlay) => render_overlay(frame, status, ui, overlay, chunks[2]),
+        None => {
+            let vis_lines = Visualizer::render(&status.spectrum, w, vis_h);
+            frame.render_widget(Paragraph::new(vis_lines), chunks[2]);
+        }
+    }
 
     // ── Layer volumes ──
-    render_layers(frame, app, chunks[4], w);
+    render_layers(frame, status, ui, chunks[4], w);
 
     // ── Master volume ──
-    render_master(frame, app, chunks[6], w);
+    render_master(frame, status, chunks[6], w);
 
     // ── Help bar ──
-    render_help(frame, chunks[8]);
+    render_help(frame, status, ui, chunks[8]);
 }
 
-fn render_header(frame: &mut Frame, app: &App, area: Rect, width: usize) {
-    let state_span = match &app.state {
+fn render_header(frame: &mut Frame, status: &Status, area: Rect, width: usize) {
+    let state_span = match &status.state {
         AppState::Loading { done, total } => Span::styled(
             format!("⏳ Loading {done}/{total}"),
             Style::default().fg(COLOR_ACCENT),
@@ -106,12 +141,16 @@ fn render_header(frame: &mut Frame, app: &App, area: Rect, width: usize) {
     };
 
     let title_text = "V I B E B A N D";
-    let state_len = match &app.state {
+    let state_len = match &status.state {
         AppState::Loading { done, total } => format!("⏳ Loading {done}/{total}").len(),
         AppState::Playing => "▶ Playing".len(),
         AppState::Paused => "⏸ Paused".len(),
     };
-    let gap = width.saturating_sub(title_text.len() + state_len);
+    let sleep_text = status
+        .sleep_secs
+        .map(|secs| format!("☾ {}  ", format_countdown(Duration::from_secs(secs))))
+        .unwrap_or_default();
+    let gap = width.saturating_sub(title_text.len() + sleep_text.chars().count() + state_len);
 
     let title_line = Line::from(vec![
         Span::styled(
@@ -121,12 +160,12 @@ fn render_header(frame: &mut Frame, app: &App, area: Rect, width: usize) {
                 .add_modifier(Modifier::BOLD),
         ),
         Span::raw(" ".repeat(gap)),
+        Span::styled(sleep_text, Style::default().fg(COLOR_TEXT)),
         state_span,
     ]);
 
     // Scene names
-    let scene_list: Vec<String> = app
-        .engine
+    let scene_list: Vec<String> = status
         .layers
         .iter()
         .map(|l| format!("{} {}", l.emoji, l.label))
@@ -147,20 +186,64 @@ fn render_header(frame: &mut Frame, app: &App, area: Rect, width: usize) {
     frame.render_widget(Paragraph::new(vec![title_line, scene_line]), area);
 }
 
-fn render_layers(frame: &mut Frame, app: &App, area: Rect, width: usize) {
+/// `m:ss`, or `h:mm:ss` from an hour up.
+fn format_countdown(left: Duration) -> String {
+    let secs = left.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn render_layers(frame: &mut Frame, status: &Status, ui: &Ui, area: Rect, width: usize) {
     let mut lines = Vec::new();
 
-    for (i, layer) in app.engine.layers.iter().enumerate() {
-        let selected =
-            i == app.selected_layer && !matches!(app.state, AppState::Loading { .. });
+    for (i, layer) in status.layers.iter().enumerate() {
+        let selected = i == ui.selected_layer && !matches!(status.state, AppState::Loading { .. });
 
         let prefix = if selected { "▸ " } else { "  " };
         let pct = (layer.volume * 100.0) as u32;
 
-        let label = format!("{}{} {}", prefix, layer.emoji, layer.label);
+        // Take number once there is more than one to choose from
+        let take = match layer.take {
+            Some(take) if layer.takes > 1 || take > 1 => format!(" #{}", take),
+            _ => String::new(),
+        };
+        let busy = match &layer.retry {
+            Some(r) => format!(" ⏳ retry {}/{}", r.retry, r.max_retries),
+            None if layer.rerolling => " ⏳ re-rolling".to_string(),
+            None => String::new(),
+        };
+
+        let label = format!("{}{} {}{}{}", prefix, layer.emoji, layer.label, take, busy);
         let vol_str = format!(" {:>3}%", pct);
 
-        let bar_total = width.saturating_sub(label.len() + vol_str.len() + 3);
+        // Mute/solo flags; the bar dims while the layer can't be heard
+        let (flag, flag_color) = match (layer.muted, layer.soloed) {
+            (true, _) => (" M", COLOR_MUTED),
+            (false, true) => (" S", COLOR_ACCENT),
+            (false, false) => ("", COLOR_DIM),
+        };
+        let stereo = Stereo {
+            pan: layer.pan,
+            width: layer.width,
+        };
+        let mut placement = match stereo.describe() {
+            s if s.is_empty() => s,
+            s => format!(" {}", s),
+        };
+        if !layer.tone.is_flat() {
+            placement.push_str(" EQ");
+        }
+        let fill_color = if layer.audible {
+            COLOR_VOL_FILL
+        } else {
+            COLOR_DIM
+        };
+
+        let bar_total =
+            width.saturating_sub(label.len() + flag.len() + placement.len() + vol_str.len() + 3);
         let filled = ((layer.volume as f64) * bar_total as f64) as usize;
         let empty = bar_total.saturating_sub(filled);
 
@@ -168,22 +251,60 @@ fn render_layers(frame: &mut Frame, app: &App, area: Rect, width: usize) {
 
         lines.push(Line::from(vec![
             Span::styled(label, Style::default().fg(label_color)),
+            Span::styled(
+                flag,
+                Style::default().fg(flag_color).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(placement, Style::default().fg(COLOR_DIM)),
             Span::raw(" "),
-            Span::styled("█".repeat(filled), Style::default().fg(COLOR_VOL_FILL)),
+            Span::styled("█".repeat(filled), Style::default().fg(fill_color)),
             Span::styled("░".repeat(empty), Style::default().fg(COLOR_VOL_EMPTY)),
             Span::styled(vol_str, Style::default().fg(COLOR_DIM)),
         ]));
     }
 
+    for pending in &status.pending {
+        let label = format!("  {} {}", pending.emoji, pending.label);
+        let busy = match &pending.retry {
+            Some(r) if r.wait_secs <= 0.0 => format!("⏳ retry {}/{}", r.retry, r.max_retries),
+            Some(r) => format!(
+                "⏳ retry {}/{} in {:.0}s",
+                r.retry,
+                r.max_retries,
+                r.wait_secs.ceil()
+            ),
+            None => "⏳ loading…".to_string(),
+        };
+        let gap = width.saturating_sub(label.len() + busy.len() + 1);
+
+        lines.push(Line::from(vec![
+            Span::styled(label, Style::default().fg(COLOR_DIM)),
+            Span::raw(" ".repeat(gap + 1)),
+            Span::styled(busy, Style::default().fg(COLOR_ACCENT)),
+        ]));
+    }
+
     frame.render_widget(Paragraph::new(lines), area);
 }
 
-fn render_master(frame: &mut Frame, app: &App, area: Rect, width: usize) {
-    let master_pct = (app.engine.master_volume * 100.0) as u32;
+fn render_master(frame: &mut Frame, status: &Status, area: Rect, width: usize) {
+    let master_pct = (status.master_volume * 100.0) as u32;
     let label = "VOL ";
     let vol_str = format!(" {:>3}%", master_pct);
-    let bar_total = width.saturating_sub(label.len() + vol_str.len() + 1);
-    let filled = ((app.engine.master_volume as f64) * bar_total as f64) as usize;
+
+    // Limiter activity, in a fixed-width slot so the bar doesn't jump
+    let reduction = status.limiter_db;
+    let (limit_str, limit_color) = if status.clipping {
+        ("CLIP".to_string(), COLOR_MUTED)
+    } else if reduction >= MIN_REDUCTION_DB {
+        (format!("-{:.1}dB", reduction), COLOR_ACCENT)
+    } else {
+        (String::new(), COLOR_DIM)
+    };
+    let limit_str = format!(" {:>7}", limit_str);
+
+    let bar_total = width.saturating_sub(label.len() + vol_str.len() + limit_str.len() + 1);
+    let filled = ((status.master_volume as f64) * bar_total as f64) as usize;
     let empty = bar_total.saturating_sub(filled);
 
     let line = Line::from(vec![
@@ -196,24 +317,210 @@ fn render_master(frame: &mut Frame, app: &App, area: Rect, width: usize) {
         Span::styled("█".repeat(filled), Style::default().fg(COLOR_VOL_FILL)),
         Span::styled("░".repeat(empty), Style::default().fg(COLOR_VOL_EMPTY)),
         Span::styled(vol_str, Style::default().fg(COLOR_DIM)),
+        Span::styled(
+            limit_str,
+            Style::default()
+                .fg(limit_color)
+                .add_modifier(Modifier::BOLD),
+        ),
     ]);
 
     frame.render_widget(Paragraph::new(vec![line]), area);
 }
 
-fn render_help(frame: &mut Frame, area: Rect) {
-    let line = Line::from(vec![
-        Span::styled("[Spc]", Style::default().fg(COLOR_ACCENT)),
-        Span::styled("⏯  ", Style::default().fg(COLOR_DIM)),
-        Span::styled("[↑↓]", Style::default().fg(COLOR_ACCENT)),
-        Span::styled("Vol ", Style::default().fg(COLOR_DIM)),
-        Span::styled("[+-]", Style::default().fg(COLOR_ACCENT)),
-        Span::styled("Master ", Style::default().fg(COLOR_DIM)),
-        Span::styled("[Tab]", Style::default().fg(COLOR_ACCENT)),
-        Span::styled("Layer ", Style::default().fg(COLOR_DIM)),
-        Span::styled("[Q]", Style::default().fg(COLOR_ACCENT)),
-        Span::styled("Quit", Style::default().fg(COLOR_DIM)),
-    ]);
+fn render_overlay(frame: &mut Frame, status: &Status, ui: &Ui, overlay: &Overlay, area: Rect) {
+    let height = area.height as usize;
+    let lines = match overlay {
+        Overlay::Picker(picker) => picker_lines(status, picker, height),
+        Overlay::Prompt(input) => input_lines("Describe a sound to add:", input),
+        Overlay::SaveMix(name) => input_lines("Save mix as:", name),
+        Overlay::Keys => KEYS
+            .iter()
+            .map(|(key, action)| {
+                Line::from(vec![
+                    Span::styled(format!("{:<10}", key), Style::default().fg(COLOR_ACCENT)),
+                    Span::styled(action.to_string(), Style::default().fg(COLOR_TEXT)),
+                ])
+            })
+            .collect(),
+        Overlay::Layer(row) => match status.layers.get(ui.selected_layer) {
+            Some(layer) => layer_panel_lines(layer, *row, area.width as usize),
+            None => Vec::new(),
+        },
+    };
+    frame.render_widget(Paragraph::new(lines), area);
+}
 
-    frame.render_widget(Paragraph::new(vec![line]), area);
+fn layer_panel_lines(layer: &LayerStatus, row: usize, width: usize) -> Vec<Line<'static>> {
+    let mut lines = vec![Line::from(Span::styled(
+        format!("{} {}", layer.emoji, layer.label),
+        Style::default()
+            .fg(COLOR_TEXT)
+            .add_modifier(Modifier::BOLD),
+    ))];
+
+    for (i, &control) in CONTROLS.iter().enumerate() {
+        let selected = i == row;
+        let prefix = if selected { "▸ " } else { "  " };
+        let (pos, value) = control_value(layer, control);
+        let name = format!("{}{:<8}", prefix, control.name());
+        let value = format!(" {:>6}", value);
+
+        // A slider with a knob at `pos`, 0 to 1
+        let track = width
+            .saturating_sub(name.chars().count() + value.len())
+            .max(3);
+        let left = (pos.clamp(0.0, 1.0) * (track - 1) as f32).round() as usize;
+        let right = track - 1 - left;
+
+        let color = if selected { COLOR_ACCENT } else { COLOR_TEXT };
+        lines.push(Line::from(vec![
+            Span::styled(name, Style::default().fg(color)),
+            Span::styled("━".repeat(left), Style::default().fg(COLOR_VOL_FILL)),
+            Span::styled("●", Style::default().fg(color)),
+            Span::styled("─".repeat(right), Style::default().fg(COLOR_VOL_EMPTY)),
+            Span::styled(value, Style::default().fg(COLOR_DIM)),
+        ]));
+    }
+    lines
+}
+
+/// Slider position (0 to 1) and readout for one of a layer's settings.
+fn control_value(layer: &LayerStatus, control: Control) -> (f32, String) {
+    let eq = |db: f32| {
+        let pos = (db + MAX_GAIN_DB) / (2.0 * MAX_GAIN_DB);
+        (pos, format!("{:+} dB", db))
+    };
+    match control {
+        Control::Volume => (layer.volume, format!("{}%", (layer.volume * 100.0) as u32)),
+        Control::Pan => {
+            let pan = (layer.pan * 100.0).round() as i32;
+            let text = match pan {
+                0 => "C".to_string(),
+                p if p < 0 => format!("L{}", -p),
+                p => format!("R{}", p),
+            };
+            ((layer.pan + 1.0) / 2.0, text)
+        }
+        Control::Width => (layer.width, format!("{}%", (layer.width * 100.0).round())),
+        Control::Low => eq(layer.tone.low),
+        Control::Mid => eq(layer.tone.mid),
+        Control::High => eq(layer.tone.high),
+    }
+}
+
+fn input_lines(title: &str, input: &str) -> Vec<Line<'static>> {
+    vec![
+        Line::from(Span::styled(
+            title.to_string(),
+            Style::default()
+                .fg(COLOR_TEXT)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(vec![
+            Span::styled("> ", Style::default().fg(COLOR_ACCENT)),
+            Span::styled(format!("{}▏", input), Style::default().fg(COLOR_TEXT)),
+        ]),
+    ]
+}
+
+fn picker_lines<'a>(status: &Status, picker: &'a Picker, height: usize) -> Vec<Line<'a>> {
+    let mut lines = vec![Line::from(vec![
+        Span::styled(
+            "Add layer: ".to_string(),
+            Style::default()
+                .fg(COLOR_TEXT)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            format!("{}▏", picker.query),
+            Style::default().fg(COLOR_ACCENT),
+        ),
+    ])];
+
+    let matches = picker.matches();
+    if matches.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  no matches".to_string(),
+            Style::default().fg(COLOR_DIM),
+        )));
+        return lines;
+    }
+
+    // Scroll so the selection stays visible
+    let rows = height.saturating_sub(1).max(1);
+    let offset = picker.selected.saturating_sub(rows - 1);
+    for (i, item) in matches.iter().enumerate().skip(offset).take(rows) {
+        let selected = i == picker.selected;
+        let prefix = if selected { "▸ " } else { "  " };
+        let playing = if status.has_layer(&item.name) {
+            "  (playing)"
+        } else {
+            ""
+        };
+        let color = if selected { COLOR_ACCENT } else { COLOR_TEXT };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{}{} {:<14}", prefix, item.emoji, item.name),
+                Style::default().fg(color),
+            ),
+            Span::styled(
+                format!("{}{}", item.label, playing),
+                Style::default().fg(COLOR_DIM),
+            ),
+        ]));
+    }
+    lines
+}
+
+fn render_help(frame: &mut Frame, status: &Status, ui: &Ui, area: Rect) {
+    if let Some(notice) = ui.notice(status) {
+        if ui.overlay.is_none() {
+            let line = Line::from(Span::styled(
+                notice.to_string(),
+                Style::default().fg(COLOR_ACCENT),
+            ));
+            frame.render_widget(Paragraph::new(vec![line]), area);
+            return;
+        }
+    }
+
+    let keys: &[(&str, &str)] = match ui.overlay {
+        Some(Overlay::Picker(_)) => &[
+            ("[↑↓]", "Select "),
+            ("[Enter]", "Add "),
+            ("[Esc]", "Cancel"),
+        ],
+        Some(Overlay::Prompt(_)) => &[("[Enter]", "Generate "), ("[Esc]", "Cancel")],
+        Some(Overlay::SaveMix(_)) => &[("[Enter]", "Save "), ("[Esc]", "Cancel")],
+        Some(Overlay::Keys) => &[("[any key]", "Close")],
+        Some(Overlay::Layer(_)) => &[
+            ("[↑↓]", "Select "),
+            ("[←→]", "Adjust "),
+            ("[0]", "Reset "),
+            ("[Tab]", "Layer "),
+            ("[Esc]", "Close"),
+        ],
+        None => &[
+            ("[Spc]", "⏯  "),
+            ("[↑↓]", "Vol "),
+            ("[+-]", "Master "),
+            ("[Tab]", "Layer "),
+            ("[A]", "Add "),
+            ("[X]", "Drop "),
+            ("[?]", "Keys "),
+            ("[Q]", "Quit"),
+        ],
+    };
+    let spans: Vec<Span> = keys
+        .iter()
+        .flat_map(|(key, action)| {
+            [
+                Span::styled(key.to_string(), Style::default().fg(COLOR_ACCENT)),
+                Span::styled(action.to_string(), Style::default().fg(COLOR_DIM)),
+            ]
+        })
+        .collect();
+
+    frame.render_widget(Paragraph::new(vec![Line::from(spans)]), area);
 }
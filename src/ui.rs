@@ -0,0 +1,383 @@
+use crate::app::NOTICE_DURATION;
+use crate::audio::DEFAULT_LAYER_VOLUME;
+use crate::control::{AppState, Command, LayerRef, LayerStatus, Status};
+use crate::picker::Picker;
+use crate::stereo::Stereo;
+use crate::tone::Tone;
+use crossterm::event::{KeyCode, KeyEvent};
+use std::time::Instant;
+
+/// Sleep timer lengths the `z` key cycles through, in minutes.
+const SLEEP_STEPS: &[u64] = &[15, 30, 45, 60, 90];
+
+/// EQ change per key press in the layer panel.
+const EQ_STEP_DB: f32 = 1.0;
+
+/// A setting the layer panel can adjust.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Volume,
+    Pan,
+    Width,
+    Low,
+    Mid,
+    High,
+}
+
+/// Panel rows, top to bottom.
+pub const CONTROLS: &[Control] = &[
+    Control::Volume,
+    Control::Pan,
+    Control::Width,
+    Control::Low,
+    Control::Mid,
+    Control::High,
+];
+
+impl Control {
+    pub fn name(self) -> &'static str {
+        match self {
+            Control::Volume => "Volume",
+            Control::Pan => "Pan",
+            Control::Width => "Width",
+            Control::Low => "Low",
+            Control::Mid => "Mid",
+            Control::High => "High",
+        }
+    }
+}
+
+/// A panel shown in place of the spectrum that takes keyboard input.
+pub enum Overlay {
+    Picker(Picker),
+    Prompt(String),
+    /// Name for a mix snapshot.
+    SaveMix(String),
+    Keys,
+    /// Settings of the selected layer, with the index of the focused row
+    /// in `CONTROLS`.
+    Layer(usize),
+}
+
+/// What the TUI shows on top of the playing mix: selection, overlays and
+/// messages. Keys become `Command`s, so the same UI drives a mix it plays
+/// itself or one playing in a daemon.
+pub struct Ui {
+    pub overlay: Option<Overlay>,
+    pub selected_layer: usize,
+    pub should_quit: bool,
+    /// Commands for the player, in the order they were given.
+    pub commands: Vec<Command>,
+    /// Short message for the help bar, and when it was set.
+    pub notice: Option<(String, Instant)>,
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Self {
+            overlay: None,
+            selected_layer: 0,
+            should_quit: false,
+            commands: Vec::new(),
+            notice: None,
+        }
+    }
+
+    pub fn notify(&mut self, msg: String) {
+        self.notice = Some((msg, Instant::now()));
+    }
+
+    /// The message to show in the help bar, if any is recent: ours, or the
+    /// player's if it is newer.
+    pub fn notice<'a>(&'a self, status: &'a Status) -> Option<&'a str> {
+        match &self.notice {
+            Some((msg, at)) if at.elapsed() < NOTICE_DURATION => Some(msg),
+            _ => status.notice.as_deref(),
+        }
+    }
+
+    /// Keep the selection on a layer after the layer list shrinks.
+    pub fn clamp_selection(&mut self, status: &Status) {
+        self.selected_layer = self
+            .selected_layer
+            .min(status.layers.len().saturating_sub(1));
+    }
+
+    /// Step the sleep timer to the next length above what is left, or off
+    /// after the longest.
+    fn cycle_sleep(&mut self, status: &Status) {
+        let left = status.sleep_secs.map(|secs| secs.div_ceil(60));
+        let next = SLEEP_STEPS
+            .iter()
+            .find(|&&m| left.is_none_or(|left| m > left));
+        self.commands.push(Command::Sleep {
+            secs: next.map(|minutes| minutes * 60),
+        });
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent, status: &Status) {
+        if matches!(status.state, AppState::Loading { .. }) {
+            if key.code == KeyCode::Char('q') {
+                self.should_quit = true;
+            }
+            return;
+        }
+
+        if let Some(overlay) = self.overlay.take() {
+            self.overlay = self.on_overlay_key(overlay, key, status);
+            return;
+        }
+
+        let layer = status.layers.get(self.selected_layer);
+        match key.code {
+            KeyCode::Char('q') => {
+                self.should_quit = true;
+            }
+            KeyCode::Char(' ') => self.commands.push(Command::Toggle),
+            KeyCode::Tab => self.select_next_layer(status),
+            KeyCode::BackTab => self.select_prev_layer(status),
+            KeyCode::Up => self.push_for(layer, |layer| Command::VolumeBy { layer, step: 0.05 }),
+            KeyCode::Down => self.push_for(layer, |layer| Command::VolumeBy { layer, step: -0.05 }),
+            KeyCode::Char('a') => {
+                self.overlay = Some(Overlay::Picker(Picker::new()));
+            }
+            KeyCode::Char('c') => {
+                self.overlay = Some(Overlay::Prompt(String::new()));
+            }
+            KeyCode::Char('s') if !status.layers.is_empty() => {
+                self.overlay = Some(Overlay::SaveMix(String::new()));
+            }
+            KeyCode::Char('?') => {
+                self.overlay = Some(Overlay::Keys);
+            }
+            KeyCode::Char('e') if layer.is_some() => {
+                self.overlay = Some(Overlay::Layer(0));
+            }
+            KeyCode::Char('z') => self.cycle_sleep(status),
+            KeyCode::Char('x') | KeyCode::Delete => {
+                self.push_for(layer, |layer| Command::Remove { layer })
+            }
+            KeyCode::Char('m') => self.push_for(layer, |layer| Command::Mute { layer }),
+            KeyCode::Char('o') => self.push_for(layer, |layer| Command::Solo { layer }),
+            KeyCode::Char('r') => self.push_for(layer, |layer| Command::Reroll { layer }),
+            KeyCode::Char('t') => self.push_for(layer, |layer| Command::NextTake { layer }),
+            KeyCode::Char('+') | KeyCode::Char('=') => {
+                self.commands.push(Command::MasterBy { step: 0.05 });
+            }
+            KeyCode::Char('-') => self.commands.push(Command::MasterBy { step: -0.05 }),
+            KeyCode::Left => self.nudge_stereo(layer, -0.1, 0.0),
+            KeyCode::Right => self.nudge_stereo(layer, 0.1, 0.0),
+            KeyCode::Char('[') => self.nudge_stereo(layer, 0.0, -0.1),
+            KeyCode::Char(']') => self.nudge_stereo(layer, 0.0, 0.1),
+            _ => {}
+        }
+    }
+
+    fn select_next_layer(&mut self, status: &Status) {
+        if !status.layers.is_empty() {
+            self.selected_layer = (self.selected_layer + 1) % status.layers.len();
+        }
+    }
+
+    fn select_prev_layer(&mut self, status: &Status) {
+        if !status.layers.is_empty() {
+            self.selected_layer = if self.selected_layer == 0 {
+                status.layers.len() - 1
+            } else {
+                self.selected_layer - 1
+            };
+        }
+    }
+
+    /// Queue a command for the selected layer, if there is one. Layers are
+    /// named by id, so the command can't land on another layer when other
+    /// clients add or remove some in the meantime.
+    fn push_for(&mut self, layer: Option<&LayerStatus>, command: impl FnOnce(LayerRef) -> Command) {
+        if let Some(layer) = layer {
+            self.commands.push(command(LayerRef::Id(layer.id)));
+        }
+    }
+
+    fn set_stereo(&mut self, layer: &LayerStatus, stereo: Stereo) {
+        self.commands.push(Command::Stereo {
+            layer: LayerRef::Id(layer.id),
+            pan: Some(stereo.pan),
+            width: Some(stereo.width),
+        });
+    }
+
+    fn set_tone(&mut self, layer: &LayerStatus, tone: Tone) {
+        self.commands.push(Command::Eq {
+            layer: LayerRef::Id(layer.id),
+            low: Some(tone.low),
+            mid: Some(tone.mid),
+            high: Some(tone.high),
+        });
+    }
+
+    fn nudge_stereo(&mut self, layer: Option<&LayerStatus>, pan: f32, width: f32) {
+        if let Some(layer) = layer {
+            // Round so repeated steps land back on exactly centre
+            self.set_stereo(
+                layer,
+                Stereo {
+                    pan: ((layer.pan + pan) * 10.0).round() / 10.0,
+                    width: ((layer.width + width) * 10.0).round() / 10.0,
+                },
+            );
+        }
+    }
+
+    /// Step a control of the selected layer up (`dir` 1) or down (-1).
+    fn adjust(&mut self, layer: &LayerStatus, control: Control, dir: f32) {
+        let mut tone = layer.tone;
+        match control {
+            Control::Volume => {
+                let step = 0.05 * dir;
+                return self.push_for(Some(layer), |layer| Command::VolumeBy { layer, step });
+            }
+            Control::Pan => return self.nudge_stereo(Some(layer), 0.1 * dir, 0.0),
+            Control::Width => return self.nudge_stereo(Some(layer), 0.0, 0.1 * dir),
+            Control::Low => tone.low += EQ_STEP_DB * dir,
+            Control::Mid => tone.mid += EQ_STEP_DB * dir,
+            Control::High => tone.high += EQ_STEP_DB * dir,
+        }
+        self.set_tone(layer, tone);
+    }
+
+    /// Put a control of the selected layer back to its default.
+    fn reset(&mut self, layer: &LayerStatus, control: Control) {
+        let mut stereo = Stereo {
+            pan: layer.pan,
+            width: layer.width,
+        };
+        let mut tone = layer.tone;
+        match control {
+            Control::Volume => {
+                let volume = DEFAULT_LAYER_VOLUME;
+                return self.push_for(Some(layer), |layer| Command::Volume { layer, volume });
+            }
+            Control::Pan => stereo.pan = Stereo::default().pan,
+            Control::Width => stereo.width = Stereo::default().width,
+            Control::Low => tone.low = Tone::default().low,
+            Control::Mid => tone.mid = Tone::default().mid,
+            Control::High => tone.high = Tone::default().high,
+        }
+        self.set_stereo(layer, stereo);
+        self.set_tone(layer, tone);
+    }
+
+    /// Handle a key while an overlay is open. Returns the overlay to keep
+    /// showing, or `None` to close it.
+    fn on_overlay_key(
+        &mut self,
+        overlay: Overlay,
+        key: KeyEvent,
+        status: &Status,
+    ) -> Option<Overlay> {
+        match overlay {
+            Overlay::Picker(mut picker) => match key.code {
+                KeyCode::Esc => None,
+                KeyCode::Enter => {
+                    if let Some(item) = picker.current() {
+                        let scene = item.name.clone();
+                        self.commands.push(Command::Add { scene });
+                    }
+                    None
+                }
+                KeyCode::Up | KeyCode::BackTab => {
+                    picker.move_selection(-1);
+                    Some(Overlay::Picker(picker))
+                }
+                KeyCode::Down | KeyCode::Tab => {
+                    picker.move_selection(1);
+                    Some(Overlay::Picker(picker))
+                }
+                KeyCode::Backspace => {
+                    picker.pop();
+                    Some(Overlay::Picker(picker))
+                }
+                KeyCode::Char(c) => {
+                    picker.push(c);
+                    Some(Overlay::Picker(picker))
+                }
+                _ => Some(Overlay::Picker(picker)),
+            },
+            Overlay::Prompt(input) => match edit_text(input, key) {
+                TextEdit::Editing(input) => Some(Overlay::Prompt(input)),
+                TextEdit::Submitted(prompt) => {
+                    self.commands.push(Command::AddPrompt { prompt });
+                    None
+                }
+                TextEdit::Cancelled => None,
+            },
+            Overlay::SaveMix(name) => match edit_text(name, key) {
+                TextEdit::Editing(name) => Some(Overlay::SaveMix(name)),
+                TextEdit::Submitted(name) => {
+                    self.commands.push(Command::SaveMix { name });
+                    None
+                }
+                TextEdit::Cancelled => None,
+            },
+            // Any key dismisses the key list
+            Overlay::Keys => None,
+            Overlay::Layer(row) => {
+                // The layer went away, e.g. removed by another client
+                let layer = status.layers.get(self.selected_layer)?;
+                let control = CONTROLS[row];
+                let row = match key.code {
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('e') => return None,
+                    KeyCode::Up => row.saturating_sub(1),
+                    KeyCode::Down => (row + 1).min(CONTROLS.len() - 1),
+                    KeyCode::Left => {
+                        self.adjust(layer, control, -1.0);
+                        row
+                    }
+                    KeyCode::Right => {
+                        self.adjust(layer, control, 1.0);
+                        row
+                    }
+                    KeyCode::Char('0') => {
+                        self.reset(layer, control);
+                        row
+                    }
+                    KeyCode::Tab => {
+                        self.select_next_layer(status);
+                        row
+                    }
+                    KeyCode::BackTab => {
+                        self.select_prev_layer(status);
+                        row
+                    }
+                    _ => row,
+                };
+                Some(Overlay::Layer(row))
+            }
+        }
+    }
+}
+
+enum TextEdit {
+    Editing(String),
+    /// Enter with non-blank input; trimmed.
+    Submitted(String),
+    Cancelled,
+}
+
+/// Line editing shared by the text overlays.
+fn edit_text(mut input: String, key: KeyEvent) -> TextEdit {
+    match key.code {
+        KeyCode::Esc => TextEdit::Cancelled,
+        KeyCode::Enter if input.trim().is_empty() => TextEdit::Cancelled,
+        KeyCode::Enter => TextEdit::Submitted(input.trim().to_string()),
+        KeyCode::Backspace => {
+            input.pop();
+            TextEdit::Editing(input)
+        }
+        KeyCode::Char(c) => {
+            input.push(c);
+            TextEdit::Editing(input)
+        }
+        _ => TextEdit::Editing(input),
+    }
+}
@@ -1,3 +1,7 @@
+use crate::events::EventSpec;
+use crate::generator::GenOverrides;
+use crate::stereo::Stereo;
+use crate::tone::Tone;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -8,10 +12,54 @@ pub struct UserPreset {
     pub emoji: String,
     pub label: String,
     pub prompt: String,
+    #[serde(flatten)]
+    pub params: GenOverrides,
+    /// Set for event presets, which scatter one-shots instead of looping.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<EventSpec>,
+    #[serde(flatten)]
+    pub stereo: Stereo,
+    #[serde(default, skip_serializing_if = "Tone::is_flat")]
+    pub tone: Tone,
+}
+
+/// A saved combination of layers with their volumes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mix {
+    pub master_volume: f32,
+    pub layers: Vec<MixLayer>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MixLayer {
+    #[serde(flatten)]
+    pub source: MixSource,
+    pub volume: f32,
+    #[serde(flatten)]
+    pub stereo: Stereo,
+    #[serde(default, skip_serializing_if = "Tone::is_flat")]
+    pub tone: Tone,
+    /// Generation settings in effect when the mix was saved.
+    #[serde(flatten)]
+    pub params: GenOverrides,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MixSource {
+    /// Built-in, noise or user preset name.
+    Scene(String),
+    /// A free-text prompt.
+    Prompt(String),
+}
+
+/// Reserved for the mixes table, so no preset may use it as a name.
+pub const MIXES_KEY: &str = "mixes";
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct UserPresets {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mixes: BTreeMap<String, Mix>,
     #[serde(flatten)]
     pub presets: BTreeMap<String, UserPreset>,
 }
@@ -40,16 +88,9 @@ pub fn save_all(presets: &UserPresets) -> Result<()> {
     Ok(())
 }
 
-pub fn save(name: &str, emoji: &str, label: &str, prompt: &str) -> Result<()> {
+pub fn save(name: &str, preset: UserPreset) -> Result<()> {
     let mut presets = load()?;
-    presets.presets.insert(
-        name.to_string(),
-        UserPreset {
-            emoji: emoji.to_string(),
-            label: label.to_string(),
-            prompt: prompt.to_string(),
-        },
-    );
+    presets.presets.insert(name.to_string(), preset);
     save_all(&presets)
 }
 
@@ -66,3 +107,23 @@ pub fn find(name: &str) -> Result<Option<UserPreset>> {
     let presets = load()?;
     Ok(presets.presets.get(name).cloned())
 }
+
+pub fn save_mix(name: &str, mix: Mix) -> Result<()> {
+    let mut presets = load()?;
+    presets.mixes.insert(name.to_string(), mix);
+    save_all(&presets)
+}
+
+pub fn remove_mix(name: &str) -> Result<bool> {
+    let mut presets = load()?;
+    let existed = presets.mixes.remove(name).is_some();
+    if existed {
+        save_all(&presets)?;
+    }
+    Ok(existed)
+}
+
+pub fn find_mix(name: &str) -> Result<Option<Mix>> {
+    let presets = load()?;
+    Ok(presets.mixes.get(name).cloned())
+}
@@ -21,6 +21,8 @@ const COLOR_HIGH: Color = Color::Red;
 
 pub struct Visualizer {
     rings: Vec<SharedRing>,
+    /// Rings of muted or soloed-out layers, left out of the analysis.
+    silent: Vec<SharedRing>,
     prev: [f64; NUM_BANDS],
     sample_rate: f64,
     fft: Arc<dyn Fft<f64>>,
@@ -33,6 +35,7 @@ impl Visualizer {
         let fft = planner.plan_fft_forward(FFT_SIZE);
         Self {
             rings: Vec::new(),
+            silent: Vec::new(),
             prev: [0.0; NUM_BANDS],
             sample_rate,
             fft,
@@ -44,6 +47,18 @@ impl Visualizer {
         self.rings.push(ring);
     }
 
+    pub fn remove_ring(&mut self, ring: &SharedRing) {
+        self.rings.retain(|r| !Arc::ptr_eq(r, ring));
+        self.silent.retain(|r| !Arc::ptr_eq(r, ring));
+    }
+
+    pub fn set_audible(&mut self, ring: &SharedRing, audible: bool) {
+        self.silent.retain(|r| !Arc::ptr_eq(r, ring));
+        if !audible {
+            self.silent.push(ring.clone());
+        }
+    }
+
     pub fn analyze(&mut self) -> [f64; NUM_BANDS] {
         let mut bands = [0.0; NUM_BANDS];
 
@@ -52,10 +67,14 @@ impl Visualizer {
             return self.prev;
         }
 
-        // Mix samples from all rings
+        // Mix samples from all audible rings
         let mut mixed = vec![0.0f32; FFT_SIZE];
         let mut active = 0;
-        for ring in &self.rings {
+        let audible = self
+            .rings
+            .iter()
+            .filter(|r| !self.silent.iter().any(|s| Arc::ptr_eq(r, s)));
+        for ring in audible {
             if let Ok(ring) = ring.lock() {
                 let samples = ring.samples(FFT_SIZE);
                 for (i, s) in samples.iter().enumerate() {
@@ -132,9 +151,9 @@ impl Visualizer {
         }
     }
 
-    pub fn render(&mut self, width: usize, height: usize) -> Vec<Line<'static>> {
-        let bands = self.analyze();
-        let bar_width = ((width + 1) / (NUM_BANDS + 1)).max(1);
+    /// Bars for band levels from `analyze`, low to high.
+    pub fn render(bands: &[f64], width: usize, height: usize) -> Vec<Line<'static>> {
+        let bar_width = ((width + 1) / (bands.len() + 1)).max(1);
         let bar_height = height.max(2);
 
         let mut lines = Vec::with_capacity(bar_height);
@@ -167,7 +186,7 @@ impl Visualizer {
                 let bar_str: String = block.repeat(bar_width);
                 spans.push(Span::styled(bar_str, Style::default().fg(color)));
 
-                if i < NUM_BANDS - 1 {
+                if i + 1 < bands.len() {
                     spans.push(Span::raw(" "));
                 }
             }
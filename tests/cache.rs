@@ -0,0 +1,207 @@
+//! The clip cache, kept in a scratch home directory.
+#![allow(dead_code)]
+
+#[path = "../src/api.rs"]
+mod api;
+#[path = "../src/cache.rs"]
+mod cache;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/generator.rs"]
+mod generator;
+#[path = "../src/loudness.rs"]
+mod loudness;
+
+use config::CacheConfig;
+use generator::{GenParams, SoundRequest};
+use std::sync::{Mutex, MutexGuard, Once};
+use std::time::Duration;
+
+const NO_LIMITS: CacheConfig = CacheConfig {
+    max_size_mb: None,
+    max_age_days: None,
+};
+
+/// Point `HOME` at a scratch directory with an empty cache. The cache is
+/// shared by the whole process, so tests take turns.
+fn empty_cache() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    static HOME: Once = Once::new();
+    let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let home = std::env::temp_dir().join(format!("vibeband-test-{}", std::process::id()));
+    HOME.call_once(|| std::env::set_var("HOME", &home));
+    let _ = std::fs::remove_dir_all(home.join(".vibeband"));
+    guard
+}
+
+fn request(prompt: &str) -> SoundRequest {
+    SoundRequest::new(prompt, GenParams::default())
+}
+
+fn key(prompt: &str) -> String {
+    cache::cache_key(&request(prompt))
+}
+
+fn cached(key: &str) -> bool {
+    cache::key_path(key).unwrap().exists()
+}
+
+/// Cache a 1000-byte clip for `prompt`, last played at `last_used`.
+fn write(prompt: &str, last_used: u64) -> String {
+    let take = cache::write_cache(&request(prompt), &[0; 1000], &NO_LIMITS).unwrap();
+    let key = cache::take_key(&key(prompt), take);
+    cache::update_manifest(|m| m.entries.get_mut(&key).unwrap().last_used_at = last_used).unwrap();
+    key
+}
+
+#[test]
+fn evicts_least_recently_played_clips_but_never_pinned_ones() {
+    let _home = empty_cache();
+    let rain = write("rain", 100);
+    let wind = write("wind", 200);
+    let fire = write("fire", 300);
+    cache::set_pinned(&rain, true).unwrap();
+
+    // Rain is the oldest, but pinned, so wind goes first
+    let evicted = cache::evict(Some(2000), None, None).unwrap();
+    assert_eq!(evicted.count, 1);
+    assert!(cached(&rain) && !cached(&wind) && cached(&fire));
+
+    let evicted = cache::evict(Some(0), None, None).unwrap();
+    assert_eq!(evicted.count, 1);
+    assert!(cached(&rain) && !cached(&fire));
+    let manifest = cache::load_manifest().unwrap();
+    assert_eq!(manifest.entries.keys().collect::<Vec<_>>(), [&rain]);
+}
+
+#[test]
+fn pins_cover_takes_generated_later() {
+    let _home = empty_cache();
+    let first = write("rain", 100);
+    cache::set_pinned(&first, true).unwrap();
+    let second = write("rain", 200);
+    assert_ne!(first, second);
+
+    cache::evict(Some(0), Some(Duration::ZERO), None).unwrap();
+    assert!(cached(&first) && cached(&second));
+
+    assert_eq!(cache::set_pinned(&second, false).unwrap(), key("rain"));
+    cache::evict(Some(0), None, None).unwrap();
+    assert!(!cached(&first) && !cached(&second));
+}
+
+#[test]
+fn limits_too_large_to_count_are_no_limits() {
+    let limits = CacheConfig {
+        max_size_mb: Some(2),
+        max_age_days: Some(3),
+    };
+    assert_eq!(limits.max_bytes(), Some(2 * 1024 * 1024));
+    assert_eq!(limits.max_age(), Some(Duration::from_secs(3 * 86400)));
+
+    let huge = CacheConfig {
+        max_size_mb: Some(u64::MAX / 1000),
+        max_age_days: Some(u64::MAX / 1000),
+    };
+    assert_eq!(huge.max_bytes(), None);
+    assert_eq!(huge.max_age(), None);
+}
+
+#[test]
+fn keeps_the_clip_just_written() {
+    let _home = empty_cache();
+    let old = write("rain", 100);
+    let new = write("wind", 200);
+    cache::evict(Some(0), None, Some(&new)).unwrap();
+    assert!(!cached(&old) && cached(&new));
+}
+
+#[test]
+fn quarantines_clips_that_fail_their_checksum() {
+    let _home = empty_cache();
+    let rain = write("rain", 100);
+    assert_eq!(
+        cache::read_take(&request("rain"), 1).unwrap(),
+        Some(vec![0; 1000])
+    );
+
+    std::fs::write(cache::key_path(&rain).unwrap(), [1; 1000]).unwrap();
+    assert_eq!(cache::read_take(&request("rain"), 1).unwrap(), None);
+    assert!(!cached(&rain));
+    assert!(!cache::load_manifest().unwrap().entries.contains_key(&rain));
+    let quarantine = cache::cache_dir().unwrap().join("quarantine");
+    assert_eq!(std::fs::read_dir(quarantine).unwrap().count(), 1);
+}
+
+#[test]
+fn reads_another_take_when_the_selected_one_is_corrupt() {
+    let _home = empty_cache();
+    write("rain", 100);
+    let second = write("rain", 200);
+    assert_eq!(cache::takes(&request("rain")).unwrap(), [1, 2]);
+    assert_eq!(cache::selected_take(&request("rain")).unwrap(), 2);
+
+    std::fs::write(cache::key_path(&second).unwrap(), b"garbled").unwrap();
+    let take = cache::read_cache(&request("rain")).unwrap().unwrap();
+    assert_eq!(take.number, 1);
+    assert_eq!(cache::takes(&request("rain")).unwrap(), [1]);
+}
+
+#[test]
+fn only_hex_keys_name_clips() {
+    let _home = empty_cache();
+    let rain = key("rain");
+    assert!(cache::is_cache_key(&rain));
+    assert!(cache::is_cache_key(&cache::take_key(&rain, 3)));
+    for bad in ["", "../config", "/etc/passwd", "ABCDEF", "rain", "a1b2.mp3"] {
+        assert!(!cache::is_cache_key(bad), "{:?}", bad);
+        assert!(cache::key_path(bad).is_err(), "{:?}", bad);
+    }
+    assert!(cache::remove_entry("../manifest").is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn leaves_writes_in_progress_alone() {
+    let _home = empty_cache();
+    let dir = cache::cache_dir().unwrap();
+    let ours = dir.join(format!("manifest.json.{}.tmp", std::process::id()));
+    // Beyond any pid Linux hands out
+    let gone = dir.join("0123abcd.mp3.4999999.tmp");
+    let stale = dir.join(format!("89abcdef.mp3.{}.tmp", std::process::id()));
+    for path in [&ours, &gone, &stale] {
+        std::fs::write(path, b"partial").unwrap();
+    }
+    let hours_ago = std::time::SystemTime::now() - Duration::from_secs(2 * 3600);
+    std::fs::File::options()
+        .write(true)
+        .open(&stale)
+        .unwrap()
+        .set_modified(hours_ago)
+        .unwrap();
+
+    assert_eq!(cache::remove_temp_files().unwrap(), 2);
+    assert!(ours.exists() && !gone.exists() && !stale.exists());
+}
+
+#[test]
+fn takes_written_at_once_get_their_own_numbers() {
+    let _home = empty_cache();
+    let writers: Vec<_> = (0..4u8)
+        .map(|i| {
+            std::thread::spawn(move || {
+                let take = cache::write_cache(&request("rain"), &[i; 1000], &NO_LIMITS).unwrap();
+                (take, i)
+            })
+        })
+        .collect();
+    let mut takes: Vec<(u32, u8)> = writers.into_iter().map(|w| w.join().unwrap()).collect();
+    takes.sort();
+    assert_eq!(takes.iter().map(|t| t.0).collect::<Vec<_>>(), [1, 2, 3, 4]);
+    for (take, i) in takes {
+        assert_eq!(
+            cache::read_take(&request("rain"), take).unwrap(),
+            Some(vec![i; 1000])
+        );
+    }
+}
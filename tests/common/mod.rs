@@ -0,0 +1,57 @@
+//! Fixtures shared by the test crates. Each crate uses only some of them.
+
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use std::f32::consts::TAU;
+use std::io::Cursor;
+
+/// `frames` of a mono sine at `hz`, peaking at `peak`.
+pub fn sine(hz: f32, peak: f32, rate: u32, frames: usize) -> Vec<f32> {
+    (0..frames)
+        .map(|i| peak * (TAU * hz * i as f32 / rate as f32).sin())
+        .collect()
+}
+
+/// Interleaved `samples` as a 16-bit WAV.
+pub fn wav(channels: u16, rate: u32, samples: &[f32]) -> Vec<u8> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate: rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut out = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut out, spec).unwrap();
+    for &s in samples {
+        writer.write_sample((s * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    out.into_inner()
+}
+
+/// The samples of a 16-bit WAV.
+pub fn unwav(data: &[u8]) -> Vec<f32> {
+    hound::WavReader::new(Cursor::new(data))
+        .unwrap()
+        .samples::<i16>()
+        .map(|s| s.unwrap() as f32 / i16::MAX as f32)
+        .collect()
+}
+
+/// A player status with rain playing and the fireplace muted.
+pub fn status<T: DeserializeOwned>() -> T {
+    serde_json::from_value(json!({
+        "state": "playing",
+        "master_volume": 0.8,
+        "layers": [
+            {"id": 1, "name": "rain", "emoji": "🌧", "label": "Rain", "volume": 0.5,
+             "muted": false, "soloed": false, "audible": true, "pan": 0.0, "width": 1.0},
+            {"id": 2, "name": "fire", "emoji": "🔥", "label": "Fireplace", "volume": 0.5,
+             "muted": true, "soloed": false, "audible": false, "pan": 0.0, "width": 1.0}
+        ],
+        "limiter_db": 0.0,
+        "clipping": false,
+        "spectrum": [0.25, 0.5, 1.0]
+    }))
+    .unwrap()
+}
@@ -0,0 +1,70 @@
+//! The control protocol as clients see it on the wire.
+#![allow(dead_code)]
+
+#[path = "../src/control.rs"]
+mod control;
+#[path = "../src/tone.rs"]
+mod tone;
+
+use control::{Command, LayerRef, Reply};
+use serde_json::json;
+
+#[test]
+fn layers_are_named_by_position_name_or_id() {
+    let layer =
+        |value| match serde_json::from_value::<Command>(json!({"cmd": "mute", "layer": value})) {
+            Ok(Command::Mute { layer }) => layer,
+            other => panic!("expected a mute, got {:?}", other),
+        };
+    assert!(matches!(layer(json!(2)), LayerRef::Position(2)));
+    assert!(matches!(layer(json!("rain")), LayerRef::Name(name) if name == "rain"));
+    assert!(matches!(layer(json!({"id": 7})), LayerRef::Id(7)));
+    assert!(serde_json::from_value::<LayerRef>(json!({"id": 7, "x": 1})).is_err());
+
+    let command = Command::VolumeBy {
+        layer: LayerRef::Id(7),
+        step: -0.5,
+    };
+    assert_eq!(
+        serde_json::to_value(&command).unwrap(),
+        json!({"cmd": "volume_by", "layer": {"id": 7}, "step": -0.5})
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn commands_and_replies_cross_the_socket() {
+    let home = std::env::temp_dir().join(format!("vibeband-test-{}", std::process::id()));
+    std::env::set_var("HOME", &home);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<control::Request>();
+    let server = control::serve(tx).unwrap();
+    assert!(control::serve(tokio::sync::mpsc::unbounded_channel().0).is_err());
+
+    let player = tokio::spawn(async move {
+        // Answer two commands, then stop
+        for _ in 0..2 {
+            let (command, reply) = rx.recv().await.unwrap();
+            let _ = reply.send(match command {
+                Command::Mute {
+                    layer: LayerRef::Name(name),
+                } if name == "rain" => Reply::ok(),
+                other => Reply::error(format!("can't {:?}", other)),
+            });
+        }
+    });
+
+    let mut client = control::Client::connect().await.unwrap();
+    let mute = |name: &str| Command::Mute {
+        layer: LayerRef::Name(name.to_string()),
+    };
+    assert!(client.send(&mute("rain")).await.unwrap().ok);
+    let error = client.send(&mute("wind")).await.unwrap_err();
+    assert!(error.to_string().starts_with("can't Mute"), "{}", error);
+    player.await.unwrap();
+    let error = client.send(&mute("rain")).await.unwrap_err();
+    assert_eq!(error.to_string(), "vibeband stopped");
+
+    drop(server);
+    assert!(control::Client::connect().await.is_err());
+    let _ = std::fs::remove_dir_all(home);
+}
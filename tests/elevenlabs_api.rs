@@ -0,0 +1,212 @@
+//! Runs `ElevenLabsClient::generate_sound` against a local mock server.
+//!
+//! vibeband is a binary-only crate, so the modules under test are compiled
+//! straight into this test crate.
+#![allow(dead_code)]
+
+#[path = "../src/api.rs"]
+mod api;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/generator.rs"]
+mod generator;
+
+use api::{ApiError, ElevenLabsClient};
+use config::{ElevenLabsConfig, RetryConfig};
+use generator::{GenParams, GenProgress, SoundRequest};
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client_for(server: &MockServer) -> ElevenLabsClient {
+    client_waiting_up_to(server, 5)
+}
+
+fn client_waiting_up_to(server: &MockServer, max_delay_ms: u64) -> ElevenLabsClient {
+    let config = ElevenLabsConfig {
+        base_url: server.uri(),
+        model_id: "test_model".to_string(),
+        timeout_secs: 5,
+        connect_timeout_secs: 5,
+        retry: RetryConfig {
+            max_retries: 2,
+            base_delay_ms: 1,
+            max_delay_ms,
+        },
+    };
+    ElevenLabsClient::with_api_key(&config, "test-key".to_string()).unwrap()
+}
+
+fn request(prompt: &str) -> SoundRequest {
+    SoundRequest::new(prompt, GenParams::default())
+}
+
+async fn api_error_status(server: &MockServer) -> StatusCode {
+    let err = client_for(server)
+        .generate_sound(&request("rain"), &|_| {})
+        .await
+        .unwrap_err();
+    err.downcast_ref::<ApiError>()
+        .expect("expected an ApiError")
+        .status
+}
+
+#[tokio::test]
+async fn success_returns_audio_bytes() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/sound-generation"))
+        .and(header("xi-api-key", "test-key"))
+        .and(body_partial_json(serde_json::json!({
+            "text": "soft rain",
+            "duration_seconds": 12.5,
+            "prompt_influence": 0.6,
+            "loop": false,
+            "model_id": "test_model",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"ID3fake-mp3".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let params = GenParams {
+        duration_seconds: 12.5,
+        prompt_influence: Some(0.6),
+        loop_audio: false,
+        model_id: "test_model".to_string(),
+    };
+    let audio = client_for(&server)
+        .generate_sound(&SoundRequest::new("soft rain", params), &|_| {})
+        .await
+        .unwrap();
+    assert_eq!(audio, b"ID3fake-mp3");
+}
+
+#[tokio::test]
+async fn base_url_trailing_slash_is_ignored() {
+    let server = MockServer::start().await;
+    Mock::given(path("/v1/sound-generation"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"ok".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = ElevenLabsConfig {
+        base_url: format!("{}/", server.uri()),
+        ..ElevenLabsConfig::default()
+    };
+    let client = ElevenLabsClient::with_api_key(&config, "k".to_string()).unwrap();
+    client
+        .generate_sound(&request("x"), &|_| {})
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn unauthorized_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401).set_body_string("invalid_api_key"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    assert_eq!(api_error_status(&server).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn rate_limit_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).set_body_string("too_many_requests"))
+        .mount(&server)
+        .await;
+
+    assert_eq!(api_error_status(&server).await, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn server_error_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let err = client_for(&server)
+        .generate_sound(&request("rain"), &|_| {})
+        .await
+        .unwrap_err();
+    let api_err = err.downcast_ref::<ApiError>().unwrap();
+    assert_eq!(api_err.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(err.to_string().contains("overloaded"));
+}
+
+#[tokio::test]
+async fn server_error_is_retried_until_success() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(502))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"audio".to_vec()))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let seen = Mutex::new(Vec::new());
+    let audio = client_for(&server)
+        .generate_sound(&request("rain"), &|p| {
+            let GenProgress::Retrying {
+                retry, max_retries, ..
+            } = p;
+            seen.lock().unwrap().push((retry, max_retries));
+        })
+        .await
+        .unwrap();
+    assert_eq!(audio, b"audio");
+    assert_eq!(*seen.lock().unwrap(), vec![(1, 2), (2, 2)]);
+}
+
+#[tokio::test]
+async fn rate_limit_honours_retry_after() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"audio".to_vec()))
+        .mount(&server)
+        .await;
+
+    let delays = Mutex::new(Vec::new());
+    let started = Instant::now();
+    client_waiting_up_to(&server, 2000)
+        .generate_sound(&request("rain"), &|p| {
+            let GenProgress::Retrying { delay, .. } = p;
+            delays.lock().unwrap().push(delay);
+        })
+        .await
+        .unwrap();
+    assert_eq!(*delays.lock().unwrap(), vec![Duration::from_secs(1)]);
+    assert!(started.elapsed() >= Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn rate_limit_longer_than_max_delay_fails() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    assert_eq!(api_error_status(&server).await, StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,76 @@
+//! One-shots scattered by an `EventSource`.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/events.rs"]
+mod events;
+
+use events::{EventSource, EventSpec};
+
+/// A tenth of a second of mono DC at `level`, as a WAV.
+fn blip(level: f32) -> Vec<u8> {
+    common::wav(1, 44100, &[level; 4410])
+}
+
+#[test]
+fn plays_events_at_the_given_spacing_and_gain() {
+    let spec = EventSpec {
+        min_gap_secs: 0.5,
+        max_gap_secs: 0.5,
+        min_gain: 1.0,
+        max_gain: 1.0,
+        pan: 0.0,
+        variations: 1,
+    };
+    spec.validate().unwrap();
+    let out: Vec<f32> = EventSource::new(&[blip(0.5)], spec)
+        .unwrap()
+        .take(2 * 2 * 44100)
+        .collect();
+
+    // Centred at an equal-power pan, so each side plays at -3dB
+    let playing: Vec<usize> = (0..out.len() / 2)
+        .filter(|&frame| out[2 * frame] != 0.0)
+        .collect();
+    for &frame in &playing {
+        assert_eq!(out[2 * frame], out[2 * frame + 1]);
+        assert!((out[2 * frame] - 0.5 * std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+    }
+    // Two seconds hold three events of 4410 frames, one every 22050
+    assert_eq!(playing.len(), 3 * 4410);
+    assert_eq!(playing[0], 22050);
+    assert_eq!(playing[4410], 2 * 22050);
+}
+
+#[test]
+fn rejects_bad_specs() {
+    let bad = [
+        EventSpec {
+            min_gap_secs: 0.0,
+            ..EventSpec::default()
+        },
+        EventSpec {
+            min_gap_secs: 30.0,
+            max_gap_secs: 10.0,
+            ..EventSpec::default()
+        },
+        EventSpec {
+            max_gain: 1.5,
+            ..EventSpec::default()
+        },
+        EventSpec {
+            pan: -0.1,
+            ..EventSpec::default()
+        },
+        EventSpec {
+            variations: 0,
+            ..EventSpec::default()
+        },
+    ];
+    assert!(EventSpec::default().validate().is_ok());
+    for spec in bad {
+        assert!(spec.validate().is_err(), "{:?}", spec);
+    }
+    assert!(EventSource::new(&[], EventSpec::default()).is_err());
+}
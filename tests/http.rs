@@ -0,0 +1,442 @@
+//! Drives the HTTP API and WebSocket on a local port, the way the web UI
+//! does.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/control.rs"]
+mod control;
+#[path = "../src/http.rs"]
+mod http;
+#[path = "../src/tone.rs"]
+mod tone;
+#[path = "../src/ws.rs"]
+mod ws;
+
+use config::HttpConfig;
+use control::{AppState, Command, LayerRef, Reply, Request, Status};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch};
+
+/// A stand-in for the player: answers status requests, refuses to quit and
+/// reports every other command it gets.
+fn fake_player() -> (
+    mpsc::UnboundedSender<Request>,
+    mpsc::UnboundedReceiver<Command>,
+) {
+    let (tx, mut requests) = mpsc::unbounded_channel::<Request>();
+    let (seen_tx, seen) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some((command, reply)) = requests.recv().await {
+            let answer = match command {
+                Command::Status => Reply {
+                    status: Some(common::status()),
+                    ..Reply::ok()
+                },
+                Command::Quit => Reply::error("not now".to_string()),
+                command => {
+                    let _ = seen_tx.send(command);
+                    Reply::ok()
+                }
+            };
+            let _ = reply.send(answer);
+        }
+    });
+    (tx, seen)
+}
+
+struct Server {
+    http: http::Http,
+    seen: mpsc::UnboundedReceiver<Command>,
+    updates: watch::Sender<Status>,
+    client: reqwest::Client,
+}
+
+impl Server {
+    async fn start() -> Self {
+        Self::start_with(None).await
+    }
+
+    async fn start_with(token: Option<&str>) -> Self {
+        let (tx, seen) = fake_player();
+        let (updates, updates_rx) = watch::channel(common::status());
+        let config = HttpConfig {
+            enabled: true,
+            listen: "127.0.0.1:0".to_string(),
+            hosts: vec!["vibe.local".to_string()],
+            token: token.map(str::to_string),
+        };
+        let http = http::serve(&config, tx, updates_rx).await.unwrap().unwrap();
+        Self {
+            http,
+            seen,
+            updates,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.http.addr(), path)
+    }
+
+    async fn get(&self, path: &str) -> (u16, Value) {
+        let response = self.client.get(self.url(path)).send().await.unwrap();
+        (response.status().as_u16(), response.json().await.unwrap())
+    }
+
+    async fn put(&self, path: &str, body: Value) -> (u16, Value) {
+        let response = self
+            .client
+            .put(self.url(path))
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        (response.status().as_u16(), response.json().await.unwrap())
+    }
+
+    fn seen(&mut self) -> Vec<Command> {
+        std::iter::from_fn(|| self.seen.try_recv().ok()).collect()
+    }
+}
+
+fn layer_name(layer: &LayerRef) -> &str {
+    match layer {
+        LayerRef::Name(name) => name,
+        other => panic!("expected a layer name, got {}", other),
+    }
+}
+
+#[tokio::test]
+async fn off_unless_enabled() {
+    let (tx, _seen) = fake_player();
+    let (_updates, updates) = watch::channel(common::status());
+    let http = http::serve(&HttpConfig::default(), tx, updates)
+        .await
+        .unwrap();
+    assert!(http.is_none());
+    assert!(HttpConfig::default().listen.starts_with("127.0.0.1:"));
+}
+
+#[tokio::test]
+async fn needs_a_token_to_listen_on_the_network() {
+    let serve = |token: Option<&str>| {
+        let (tx, _seen) = fake_player();
+        let (_updates, updates) = watch::channel(common::status());
+        let config = HttpConfig {
+            enabled: true,
+            listen: "0.0.0.0:0".to_string(),
+            token: token.map(str::to_string),
+            ..HttpConfig::default()
+        };
+        async move { http::serve(&config, tx, updates).await }
+    };
+    let error = serve(None).await.err().expect("served without a token");
+    assert!(error.to_string().contains("token"), "{}", error);
+    assert!(serve(Some("sesame")).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn serves_the_web_ui() {
+    let server = Server::start().await;
+    let response = reqwest::get(server.url("/")).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert!(response.text().await.unwrap().contains("/api/ws"));
+}
+
+#[tokio::test]
+async fn reads_state_and_layers() {
+    let server = Server::start().await;
+
+    let (code, status) = server.get("/api/status").await;
+    assert_eq!(code, 200);
+    assert_eq!(status["state"], "playing");
+    assert_eq!(status["master_volume"], 0.8);
+
+    let (code, layers) = server.get("/api/layers").await;
+    assert_eq!(code, 200);
+    assert_eq!(layers.as_array().unwrap().len(), 2);
+
+    let (code, layer) = server.get("/api/layers/2").await;
+    assert_eq!(code, 200);
+    assert_eq!(layer["name"], "fire");
+    let (_, layer) = server.get("/api/layers/rain").await;
+    assert_eq!(layer["label"], "Rain");
+
+    let (code, reply) = server.get("/api/layers/3").await;
+    assert_eq!(code, 404);
+    assert_eq!(reply["ok"], false);
+    let (code, _) = server.get("/api/nope").await;
+    assert_eq!(code, 404);
+}
+
+#[tokio::test]
+async fn sets_volumes_and_state() {
+    let mut server = Server::start().await;
+
+    let (code, reply) = server.put("/api/master", json!({"volume": 1.5})).await;
+    assert_eq!((code, reply), (200, json!({"ok": true})));
+    match server.seen().as_slice() {
+        [Command::Master { volume }] => assert_eq!(*volume, 1.0),
+        other => panic!("expected a master volume change, got {:?}", other),
+    }
+
+    // fire is muted already, so only the volume and solo change
+    let update = json!({"volume": 0.3, "muted": true, "soloed": true});
+    let (code, _) = server.put("/api/layers/fire", update).await;
+    assert_eq!(code, 200);
+    match server.seen().as_slice() {
+        [Command::Volume { layer, volume }, Command::Solo { layer: soloed }] => {
+            assert_eq!(layer_name(layer), "fire");
+            assert_eq!(*volume, 0.3);
+            assert_eq!(layer_name(soloed), "fire");
+        }
+        other => panic!("expected a volume change and a solo, got {:?}", other),
+    }
+
+    let (code, _) = server.put("/api/state", json!({"state": "paused"})).await;
+    assert_eq!(code, 200);
+    let (code, _) = server.put("/api/state", json!({"state": "playing"})).await;
+    assert_eq!(code, 200);
+    assert!(matches!(
+        server.seen().as_slice(),
+        [Command::Pause, Command::Play]
+    ));
+}
+
+#[tokio::test]
+async fn takes_any_control_command() {
+    let mut server = Server::start().await;
+    let response = server
+        .client
+        .post(server.url("/api/command"))
+        .json(&json!({"cmd": "add", "scene": "fire"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(matches!(
+        server.seen().as_slice(),
+        [Command::Add { scene }] if scene == "fire"
+    ));
+
+    let response = server
+        .client
+        .post(server.url("/api/command"))
+        .json(&json!({"cmd": "quit"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let reply: Value = response.json().await.unwrap();
+    assert_eq!(reply, json!({"ok": false, "error": "not now"}));
+}
+
+#[tokio::test]
+async fn rejects_bad_bodies() {
+    let mut server = Server::start().await;
+    let (code, reply) = server.put("/api/master", json!({"level": 0.5})).await;
+    assert_eq!(code, 400);
+    assert!(reply["error"].as_str().unwrap().contains("volume"));
+    let (code, _) = server.put("/api/state", json!({"state": "loud"})).await;
+    assert_eq!(code, 400);
+    let (code, _) = server
+        .put(
+            "/api/state",
+            json!({"state": "loading", "done": 0, "total": 1}),
+        )
+        .await;
+    assert_eq!(code, 400);
+    assert!(server.seen().is_empty());
+}
+
+#[test]
+fn accept_key_matches_the_rfc() {
+    assert_eq!(
+        ws::accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+async fn read_text(stream: &mut TcpStream) -> Value {
+    let frame = tokio::time::timeout(Duration::from_secs(5), ws::read_frame(stream))
+        .await
+        .expect("nothing was pushed")
+        .unwrap();
+    assert_eq!(frame.opcode, ws::TEXT);
+    serde_json::from_slice(&frame.payload).unwrap()
+}
+
+/// A masked frame, as clients must send.
+fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [1, 2, 3, 4];
+    let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+/// Ask for a WebSocket with extra `headers`, returning the response head.
+async fn upgrade(stream: &mut TcpStream, port: u16, headers: &str) -> String {
+    let request = format!(
+        "GET /api/ws HTTP/1.1\r\nHost: localhost:{}\r\nUpgrade: websocket\r\n\
+         Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Version: 13\r\n{}\r\n",
+        port, headers
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    String::from_utf8(head).unwrap().to_lowercase()
+}
+
+#[tokio::test]
+async fn websocket_pushes_status_and_levels() {
+    let server = Server::start().await;
+    let mut stream = TcpStream::connect(server.http.addr()).await.unwrap();
+    let head = upgrade(&mut stream, server.http.addr().port(), "").await;
+    assert!(head.starts_with("http/1.1 101"), "{}", head);
+    assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="));
+
+    let first = read_text(&mut stream).await;
+    assert_eq!(first["type"], "status");
+    assert_eq!(first["state"], "playing");
+    assert_eq!(first["layers"][0]["name"], "rain");
+    let levels = read_text(&mut stream).await;
+    assert_eq!(
+        levels,
+        json!({"type": "levels", "bands": [0.25, 0.5, 1.0], "limiter_db": 0.0, "clipping": false})
+    );
+
+    // Only the levels go out while nothing else changes
+    let mut next: Status = common::status();
+    next.spectrum = vec![1.0, 0.0];
+    server.updates.send_replace(next.clone());
+    let levels = read_text(&mut stream).await;
+    assert_eq!(levels["bands"], json!([1.0, 0.0]));
+
+    next.state = AppState::Paused;
+    server.updates.send_replace(next);
+    let changed = read_text(&mut stream).await;
+    assert_eq!(changed["type"], "status");
+    assert_eq!(changed["state"], "paused");
+    assert_eq!(read_text(&mut stream).await["type"], "levels");
+
+    stream
+        .write_all(&client_frame(ws::PING, b"hi"))
+        .await
+        .unwrap();
+    let pong = ws::read_frame(&mut stream).await.unwrap();
+    assert_eq!(
+        (pong.opcode, pong.payload.as_slice()),
+        (ws::PONG, &b"hi"[..])
+    );
+
+    stream
+        .write_all(&client_frame(ws::CLOSE, &[0x03, 0xe8]))
+        .await
+        .unwrap();
+    let close = ws::read_frame(&mut stream).await.unwrap();
+    assert_eq!(close.opcode, ws::CLOSE);
+}
+
+#[tokio::test]
+async fn plain_get_of_the_websocket_is_refused() {
+    let server = Server::start().await;
+    let (code, _) = server.get("/api/ws").await;
+    assert_eq!(code, 400);
+}
+
+#[tokio::test]
+async fn refuses_other_sites() {
+    let mut server = Server::start().await;
+    let own = format!("http://{}", server.http.addr());
+    let put = |origin: &str| {
+        server
+            .client
+            .put(server.url("/api/master"))
+            .header("origin", origin)
+            .json(&json!({"volume": 0.5}))
+            .send()
+    };
+    assert_eq!(put("http://evil.example").await.unwrap().status(), 403);
+    assert_eq!(put(&own).await.unwrap().status(), 200);
+    assert_eq!(server.seen().len(), 1);
+
+    // A form post needs no preflight, so only JSON is taken
+    let response = server
+        .client
+        .post(server.url("/api/command"))
+        .header("content-type", "text/plain")
+        .body(r#"{"cmd": "pause"}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 415);
+    assert!(server.seen().is_empty());
+
+    let mut stream = TcpStream::connect(server.http.addr()).await.unwrap();
+    let port = server.http.addr().port();
+    let head = upgrade(&mut stream, port, "Origin: http://evil.example\r\n").await;
+    assert!(head.starts_with("http/1.1 403"), "{}", head);
+}
+
+#[tokio::test]
+async fn refuses_other_host_names() {
+    let server = Server::start().await;
+    let port = server.http.addr().port();
+    let get = |host: String| {
+        server
+            .client
+            .get(server.url("/api/status"))
+            .header("host", host)
+            .send()
+    };
+    // A rebound DNS name still carries its own name in Host
+    let code = |host: String| async { get(host).await.unwrap().status().as_u16() };
+    assert_eq!(code(format!("evil.example:{}", port)).await, 403);
+    assert_eq!(code("localhost:1".to_string()).await, 403);
+    assert_eq!(code(format!("localhost:{}", port)).await, 200);
+    assert_eq!(code(format!("127.0.0.1:{}", port)).await, 200);
+    assert_eq!(code(format!("vibe.local:{}", port)).await, 200);
+}
+
+#[tokio::test]
+async fn takes_a_token_when_set() {
+    let server = Server::start_with(Some("s3cret")).await;
+    let (code, reply) = server.get("/api/status").await;
+    assert_eq!(code, 401);
+    assert_eq!(reply["ok"], false);
+    let response = server
+        .client
+        .get(server.url("/api/status"))
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = server
+        .client
+        .get(server.url("/api/status"))
+        .bearer_auth("s3cret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let (code, _) = server.get("/api/status?token=s3cret").await;
+    assert_eq!(code, 200);
+    // The page itself holds nothing secret
+    assert_eq!(reqwest::get(server.url("/")).await.unwrap().status(), 200);
+}
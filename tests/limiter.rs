@@ -0,0 +1,47 @@
+//! The master bus limiter and soft clipper.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/limiter.rs"]
+mod limiter;
+
+use limiter::{Limiter, LimiterMeter, CEILING};
+use rodio::buffer::SamplesBuffer;
+
+const RATE: u32 = 44100;
+
+/// Half a second of a stereo sine at `peak`.
+fn sine(peak: f32) -> SamplesBuffer<f32> {
+    let samples = common::sine(440.0, peak, RATE, RATE as usize / 2)
+        .into_iter()
+        .flat_map(|s| [s, -s])
+        .collect::<Vec<f32>>();
+    SamplesBuffer::new(2, RATE, samples)
+}
+
+#[test]
+fn holds_a_full_scale_input_under_the_ceiling() {
+    for peak in [1.0, 2.0] {
+        let meter = LimiterMeter::new();
+        let out: Vec<f32> = Limiter::new(sine(peak), meter.clone()).collect();
+        let loudest = out.iter().map(|s| s.abs()).fold(0.0, f32::max);
+        assert!(loudest <= CEILING, "peak {}: {}", peak, loudest);
+        assert!(meter.reduction_db() > 0.0);
+        assert!(!meter.take_clipped());
+    }
+}
+
+#[test]
+fn passes_quiet_audio_through_delayed() {
+    let meter = LimiterMeter::new();
+    let input: Vec<f32> = sine(0.5).collect();
+    let out: Vec<f32> = Limiter::new(sine(0.5), meter.clone()).collect();
+    let start = |samples: &[f32]| samples.iter().position(|&s| s != 0.0).unwrap();
+    let delay = start(&out) - start(&input);
+    assert!(delay > 0 && delay % 2 == 0);
+    for (o, i) in out[delay..].iter().zip(&input) {
+        assert!((o - i).abs() < 1e-6);
+    }
+    assert_eq!(meter.reduction_db(), 0.0);
+}
@@ -0,0 +1,54 @@
+//! EBU R128 loudness as measured for normalization.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/loudness.rs"]
+mod loudness;
+
+use rodio::buffer::SamplesBuffer;
+
+const RATE: u32 = 48000;
+
+/// `secs` of a 1kHz sine peaking at `dbfs`, in every channel.
+fn sine(dbfs: f32, channels: u16, secs: f32) -> SamplesBuffer<f32> {
+    let peak = 10f32.powf(dbfs / 20.0);
+    let samples = common::sine(1000.0, peak, RATE, (secs * RATE as f32) as usize)
+        .into_iter()
+        .flat_map(|s| std::iter::repeat_n(s, channels as usize))
+        .collect::<Vec<f32>>();
+    SamplesBuffer::new(channels, RATE, samples)
+}
+
+#[test]
+fn a_1khz_sine_at_minus_20_dbfs_measures_minus_23_lufs() {
+    let lufs = loudness::integrated(sine(-20.0, 1, 3.0)).unwrap();
+    assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+
+    // Channels add up, so the same sine in stereo is 3dB louder
+    let lufs = loudness::integrated(sine(-20.0, 2, 3.0)).unwrap();
+    assert!((lufs + 20.0).abs() < 0.1, "{}", lufs);
+}
+
+#[test]
+fn measures_encoded_clips() {
+    let clip = common::wav(1, RATE, &sine(-20.0, 1, 3.0).collect::<Vec<f32>>());
+    let lufs = loudness::integrated_clip(&clip).unwrap();
+    assert!((lufs + 23.0).abs() < 0.1, "{}", lufs);
+    assert_eq!(loudness::integrated_clip(b"not audio"), None);
+}
+
+#[test]
+fn silence_and_short_clips_have_no_loudness() {
+    let silence = SamplesBuffer::new(1, RATE, vec![0.0; 3 * RATE as usize]);
+    assert_eq!(loudness::integrated(silence), None);
+    assert_eq!(loudness::integrated(sine(-20.0, 1, 0.3)), None);
+}
+
+#[test]
+fn gain_brings_clips_to_the_target_with_a_capped_boost() {
+    assert!((loudness::gain_for(-14.0, -20.0) - 0.5012).abs() < 1e-3);
+    assert!((loudness::gain_for(-26.0, -20.0) - 1.9953).abs() < 1e-3);
+    // Near-silence is boosted by 12dB at most
+    assert!((loudness::gain_for(-60.0, -20.0) - 3.9811).abs() < 1e-3);
+}
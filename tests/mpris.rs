@@ -0,0 +1,209 @@
+//! Drives the MPRIS interface over a private D-Bus session bus, the way a
+//! desktop media widget would.
+//!
+//! Needs `dbus-daemon`; the tests pass with a note when it isn't installed.
+#![cfg(target_os = "linux")]
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/control.rs"]
+mod control;
+#[path = "../src/mpris.rs"]
+mod mpris;
+#[path = "../src/tone.rs"]
+mod tone;
+
+use control::{AppState, Command, Reply, Request, Status};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Stdio};
+use tokio::sync::mpsc;
+use zbus::proxy::CacheProperties;
+use zbus::zvariant::OwnedValue;
+use zbus::Connection;
+
+/// A `dbus-daemon` of our own, killed on drop.
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn spawn() -> Option<Self> {
+        let mut daemon = std::process::Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .inspect_err(|e| eprintln!("skipping: can't run dbus-daemon ({})", e))
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(Self {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    async fn connect(&self) -> Connection {
+        zbus::connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// A stand-in for the player: answers requests from `status` and reports
+/// every other command it gets.
+fn fake_player(
+    mut status: Status,
+) -> (
+    mpsc::UnboundedSender<Request>,
+    mpsc::UnboundedReceiver<Command>,
+) {
+    let (tx, mut requests) = mpsc::unbounded_channel::<Request>();
+    let (seen_tx, seen) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some((command, reply)) = requests.recv().await {
+            let answer = match &command {
+                Command::Status => Reply {
+                    status: Some(status.clone()),
+                    ..Reply::ok()
+                },
+                Command::Toggle => {
+                    status.state = match status.state {
+                        AppState::Paused => AppState::Playing,
+                        _ => AppState::Paused,
+                    };
+                    Reply::ok()
+                }
+                Command::Master { volume } => {
+                    status.master_volume = *volume;
+                    Reply::ok()
+                }
+                Command::Quit => Reply::error("not now".to_string()),
+                _ => Reply::ok(),
+            };
+            if !matches!(command, Command::Status) {
+                let _ = seen_tx.send(command);
+            }
+            let _ = reply.send(answer);
+        }
+    });
+    (tx, seen)
+}
+
+async fn player_proxy<'a>(connection: &Connection, name: &'a str) -> zbus::Proxy<'a> {
+    zbus::proxy::Builder::new(connection)
+        .destination(name)
+        .unwrap()
+        .path("/org/mpris/MediaPlayer2")
+        .unwrap()
+        .interface("org.mpris.MediaPlayer2.Player")
+        .unwrap()
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn media_keys_control_playback_and_volume() {
+    let Some(bus) = Bus::spawn() else { return };
+    let (tx, mut seen) = fake_player(common::status());
+    let _mpris = mpris::serve_on(bus.connect().await, tx).await.unwrap();
+
+    let client = bus.connect().await;
+    let player = player_proxy(&client, "org.mpris.MediaPlayer2.vibeband").await;
+
+    let state: String = player.get_property("PlaybackStatus").await.unwrap();
+    assert_eq!(state, "Playing");
+    player.call_method("PlayPause", &()).await.unwrap();
+    assert!(matches!(seen.recv().await, Some(Command::Toggle)));
+    let state: String = player.get_property("PlaybackStatus").await.unwrap();
+    assert_eq!(state, "Paused");
+
+    player.call_method("Play", &()).await.unwrap();
+    assert!(matches!(seen.recv().await, Some(Command::Play)));
+    player.call_method("Stop", &()).await.unwrap();
+    assert!(matches!(seen.recv().await, Some(Command::Pause)));
+
+    player.set_property("Volume", 1.5f64).await.unwrap();
+    match seen.recv().await {
+        Some(Command::Master { volume }) => assert_eq!(volume, 1.0),
+        other => panic!("expected a master volume change, got {:?}", other),
+    }
+    assert!(player.set_property("Volume", f64::NAN).await.is_err());
+    player.set_property("Volume", 0.25f64).await.unwrap();
+    // The NaN never got through
+    match seen.recv().await {
+        Some(Command::Master { volume }) => assert_eq!(volume, 0.25),
+        other => panic!("expected a master volume change, got {:?}", other),
+    }
+    let volume: f64 = player.get_property("Volume").await.unwrap();
+    assert_eq!(volume, 0.25);
+}
+
+#[tokio::test]
+async fn metadata_names_the_scenes_playing() {
+    let Some(bus) = Bus::spawn() else { return };
+    let (tx, _seen) = fake_player(common::status());
+    let _mpris = mpris::serve_on(bus.connect().await, tx).await.unwrap();
+
+    let client = bus.connect().await;
+    let player = player_proxy(&client, "org.mpris.MediaPlayer2.vibeband").await;
+    let metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").await.unwrap();
+    let title = String::try_from(metadata["xesam:title"].clone()).unwrap();
+    assert_eq!(title, "Rain + Fireplace");
+    assert!(metadata.contains_key("mpris:trackid"));
+}
+
+#[tokio::test]
+async fn player_errors_reach_the_caller() {
+    let Some(bus) = Bus::spawn() else { return };
+    let (tx, _seen) = fake_player(common::status());
+    let _mpris = mpris::serve_on(bus.connect().await, tx).await.unwrap();
+
+    let client = bus.connect().await;
+    let root = zbus::Proxy::new(
+        &client,
+        "org.mpris.MediaPlayer2.vibeband",
+        "/org/mpris/MediaPlayer2",
+        "org.mpris.MediaPlayer2",
+    )
+    .await
+    .unwrap();
+    let identity: String = root.get_property("Identity").await.unwrap();
+    assert_eq!(identity, "vibeband");
+    let err = root.call_method("Quit", &()).await.unwrap_err();
+    assert!(err.to_string().contains("not now"), "{}", err);
+}
+
+#[tokio::test]
+async fn second_instance_gets_its_own_name() {
+    let Some(bus) = Bus::spawn() else { return };
+    let (tx, _seen) = fake_player(common::status());
+    let _first = mpris::serve_on(bus.connect().await, tx.clone())
+        .await
+        .unwrap();
+    let _second = mpris::serve_on(bus.connect().await, tx).await.unwrap();
+
+    let client = bus.connect().await;
+    let name = format!(
+        "org.mpris.MediaPlayer2.vibeband.instance{}",
+        std::process::id()
+    );
+    let player = player_proxy(&client, &name).await;
+    let state: String = player.get_property("PlaybackStatus").await.unwrap();
+    assert_eq!(state, "Playing");
+}
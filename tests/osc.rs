@@ -0,0 +1,306 @@
+//! Decodes OSC packets and drives the OSC listener over loopback UDP, the
+//! way a control surface would.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/control.rs"]
+mod control;
+#[path = "../src/osc.rs"]
+mod osc;
+#[path = "../src/tone.rs"]
+mod tone;
+
+use config::OscConfig;
+use control::{Command, LayerRef, Reply, Request};
+use osc::{Arg, Message};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, watch};
+
+fn message(addr: &str, args: Vec<Arg>) -> Message {
+    Message {
+        addr: addr.to_string(),
+        args,
+    }
+}
+
+fn layer_name(layer: &LayerRef) -> &str {
+    match layer {
+        LayerRef::Name(name) => name,
+        other => panic!("expected a layer name, got {}", other),
+    }
+}
+
+#[test]
+fn encodes_like_the_spec_example() {
+    let packet = osc::encode(&message("/oscillator/4/frequency", vec![Arg::Float(440.0)]));
+    let mut expected = b"/oscillator/4/frequency\0,f\0\0".to_vec();
+    expected.extend_from_slice(&[0x43, 0xdc, 0x00, 0x00]);
+    assert_eq!(packet, expected);
+}
+
+#[test]
+fn round_trips_every_argument_type() {
+    let original = message(
+        "/vibeband/test",
+        vec![
+            Arg::Int(-7),
+            Arg::Float(0.5),
+            Arg::Str("rain".to_string()),
+            Arg::Bool(true),
+            Arg::Bool(false),
+        ],
+    );
+    let packet = osc::encode(&original);
+    assert_eq!(packet.len() % 4, 0);
+    assert_eq!(osc::decode(&packet).unwrap(), vec![original]);
+}
+
+#[test]
+fn flattens_bundles() {
+    let first = osc::encode(&message("/vibeband/master", vec![Arg::Float(0.5)]));
+    let second = osc::encode(&message("/vibeband/pause", vec![]));
+    let mut packet = b"#bundle\0".to_vec();
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    for element in [&first, &second] {
+        packet.extend_from_slice(&(element.len() as i32).to_be_bytes());
+        packet.extend_from_slice(element);
+    }
+    let messages = osc::decode(&packet).unwrap();
+    let addrs: Vec<&str> = messages.iter().map(|m| m.addr.as_str()).collect();
+    assert_eq!(addrs, ["/vibeband/master", "/vibeband/pause"]);
+}
+
+#[test]
+fn rejects_garbage() {
+    assert!(osc::decode(b"").is_err());
+    assert!(osc::decode(b"no slash\0\0\0\0").is_err());
+    assert!(osc::decode(b"/vibeband/master\0\0\0\0,f\0\0\0\0").is_err());
+    assert!(osc::decode(b"/vibeband/master\0\0\0\0,b\0\0").is_err());
+}
+
+#[test]
+fn maps_addresses_to_commands() {
+    let status = common::status();
+    let command = |addr: &str, args: Vec<Arg>| osc::command(&message(addr, args), &status);
+
+    match command("/vibeband/master", vec![Arg::Float(1.5)]).unwrap() {
+        Some(Command::Master { volume }) => assert_eq!(volume, 1.0),
+        other => panic!("expected a master volume change, got {:?}", other),
+    }
+    match command("/vibeband/layer/2/volume", vec![Arg::Float(0.3)]).unwrap() {
+        Some(Command::Volume { layer, volume }) => {
+            assert_eq!(layer_name(&layer), "fire");
+            assert_eq!(volume, 0.3);
+        }
+        other => panic!("expected a layer volume change, got {:?}", other),
+    }
+    match command("/vibeband/layer/rain/volume", vec![Arg::Int(0)]).unwrap() {
+        Some(Command::Volume { layer, volume }) => {
+            assert_eq!(layer_name(&layer), "rain");
+            assert_eq!(volume, 0.0);
+        }
+        other => panic!("expected a layer volume change, got {:?}", other),
+    }
+
+    assert!(matches!(
+        command("/vibeband/pause", vec![]).unwrap(),
+        Some(Command::Toggle)
+    ));
+    assert!(matches!(
+        command("/vibeband/pause", vec![Arg::Float(1.0)]).unwrap(),
+        Some(Command::Pause)
+    ));
+    assert!(matches!(
+        command("/vibeband/pause", vec![Arg::Bool(false)]).unwrap(),
+        Some(Command::Play)
+    ));
+}
+
+#[test]
+fn switches_only_change_what_differs() {
+    let status = common::status();
+    let command = |addr: &str, args: Vec<Arg>| osc::command(&message(addr, args), &status);
+
+    // fire is muted already, rain isn't
+    assert!(command("/vibeband/layer/2/mute", vec![Arg::Float(1.0)])
+        .unwrap()
+        .is_none());
+    assert!(matches!(
+        command("/vibeband/layer/2/mute", vec![Arg::Float(0.0)]).unwrap(),
+        Some(Command::Mute { .. })
+    ));
+    assert!(matches!(
+        command("/vibeband/layer/1/mute", vec![]).unwrap(),
+        Some(Command::Mute { .. })
+    ));
+    assert!(matches!(
+        command("/vibeband/layer/1/solo", vec![Arg::Int(1)]).unwrap(),
+        Some(Command::Solo { .. })
+    ));
+}
+
+#[test]
+fn reports_bad_messages() {
+    let status = common::status();
+    let error = |addr: &str, args: Vec<Arg>| {
+        osc::command(&message(addr, args), &status)
+            .unwrap_err()
+            .to_string()
+    };
+    assert!(error("/vibeband/layer/3/volume", vec![Arg::Float(0.5)]).contains("No layer '3'"));
+    assert!(error("/vibeband/layer/0/mute", vec![]).contains("No layer '0'"));
+    assert!(error("/vibeband/master", vec![]).contains("needs a level"));
+    assert!(error("/vibeband/master", vec![Arg::Str("loud".into())]).contains("needs a level"));
+    assert!(error("/vibeband/master", vec![Arg::Float(f32::NAN)]).contains("needs a level"));
+    assert!(
+        error("/vibeband/layer/1/volume", vec![Arg::Float(f32::INFINITY)])
+            .contains("needs a level")
+    );
+    assert!(error("/vibeband/layer/1/pan", vec![Arg::Float(0.5)]).contains("unknown"));
+    assert!(error("/other/master", vec![Arg::Float(0.5)]).contains("unknown"));
+}
+
+#[test]
+fn keeps_only_the_latest_level_per_address() {
+    let messages = vec![
+        message("/vibeband/master", vec![Arg::Float(0.1)]),
+        message("/vibeband/layer/1/mute", vec![]),
+        message("/vibeband/layer/1/volume", vec![Arg::Float(0.2)]),
+        message("/vibeband/master", vec![Arg::Float(0.3)]),
+        message("/vibeband/layer/1/mute", vec![]),
+        message("/vibeband/layer/2/volume", vec![Arg::Float(0.4)]),
+    ];
+    let kept: Vec<(String, Vec<Arg>)> = osc::coalesce(messages)
+        .into_iter()
+        .map(|m| (m.addr, m.args))
+        .collect();
+    assert_eq!(
+        kept,
+        vec![
+            ("/vibeband/layer/1/mute".to_string(), vec![]),
+            (
+                "/vibeband/layer/1/volume".to_string(),
+                vec![Arg::Float(0.2)]
+            ),
+            ("/vibeband/master".to_string(), vec![Arg::Float(0.3)]),
+            ("/vibeband/layer/1/mute".to_string(), vec![]),
+            (
+                "/vibeband/layer/2/volume".to_string(),
+                vec![Arg::Float(0.4)]
+            ),
+        ]
+    );
+}
+
+/// A stand-in for the player: answers status requests and reports every
+/// other command it gets.
+fn fake_player() -> (
+    mpsc::UnboundedSender<Request>,
+    mpsc::UnboundedReceiver<Command>,
+) {
+    let (tx, mut requests) = mpsc::unbounded_channel::<Request>();
+    let (seen_tx, seen) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some((command, reply)) = requests.recv().await {
+            let answer = match command {
+                Command::Status => Reply {
+                    status: Some(common::status()),
+                    ..Reply::ok()
+                },
+                command => {
+                    let _ = seen_tx.send(command);
+                    Reply::ok()
+                }
+            };
+            let _ = reply.send(answer);
+        }
+    });
+    (tx, seen)
+}
+
+fn free_port() -> u16 {
+    std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+async fn nothing_runs_unless_configured() {
+    let (tx, _seen) = fake_player();
+    let (_updates_tx, updates) = watch::channel(common::status());
+    let osc = osc::serve(&OscConfig::default(), tx, updates)
+        .await
+        .unwrap();
+    assert!(osc.is_none());
+}
+
+#[tokio::test]
+async fn control_surface_drives_the_player() {
+    let (tx, mut seen) = fake_player();
+    let (_updates_tx, updates) = watch::channel(common::status());
+    let listen = format!("127.0.0.1:{}", free_port());
+    let config = OscConfig {
+        listen: Some(listen.clone()),
+        feedback: None,
+    };
+    let _osc = osc::serve(&config, tx, updates).await.unwrap().unwrap();
+
+    let surface = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    // Bad packets and unknown addresses are skipped
+    surface.send_to(b"junk", &listen).await.unwrap();
+    for m in [
+        message("/vibeband/nope", vec![]),
+        message("/vibeband/master", vec![Arg::Float(0.4)]),
+        message("/vibeband/layer/1/mute", vec![Arg::Float(1.0)]),
+    ] {
+        surface.send_to(&osc::encode(&m), &listen).await.unwrap();
+    }
+
+    match next(&mut seen).await {
+        Some(Command::Master { volume }) => assert_eq!(volume, 0.4),
+        other => panic!("expected a master volume change, got {:?}", other),
+    }
+    match next(&mut seen).await {
+        Some(Command::Mute { layer }) => assert_eq!(layer_name(&layer), "rain"),
+        other => panic!("expected a mute, got {:?}", other),
+    }
+}
+
+async fn next(seen: &mut mpsc::UnboundedReceiver<Command>) -> Option<Command> {
+    tokio::time::timeout(Duration::from_secs(5), seen.recv())
+        .await
+        .expect("no command arrived")
+}
+
+#[tokio::test]
+async fn band_levels_go_out_as_feedback() {
+    let (tx, _seen) = fake_player();
+    let (updates_tx, updates) = watch::channel(common::status());
+    let visuals = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let config = OscConfig {
+        listen: None,
+        feedback: Some(visuals.local_addr().unwrap().to_string()),
+    };
+    let _osc = osc::serve(&config, tx, updates).await.unwrap().unwrap();
+
+    updates_tx.send_replace(common::status());
+    let mut buf = [0; 1024];
+    let (len, _) = tokio::time::timeout(Duration::from_secs(5), visuals.recv_from(&mut buf))
+        .await
+        .expect("no feedback arrived")
+        .unwrap();
+    let messages = osc::decode(&buf[..len]).unwrap();
+    assert_eq!(
+        messages,
+        vec![message(
+            osc::BANDS_ADDR,
+            vec![Arg::Float(0.25), Arg::Float(0.5), Arg::Float(1.0)]
+        )]
+    );
+}
@@ -0,0 +1,62 @@
+//! Arguments as typed on the command line.
+#![allow(dead_code)]
+
+#[path = "../src/parse.rs"]
+mod parse;
+
+use parse::{parse_level, parse_mix_layer, parse_span, parse_span_range};
+use std::time::Duration;
+
+#[test]
+fn spans_take_a_unit() {
+    assert_eq!(parse_span("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_span(" 45m "), Ok(Duration::from_secs(45 * 60)));
+    assert_eq!(parse_span("1.5h"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse_span("30d"), Ok(Duration::from_secs(30 * 86400)));
+    for bad in ["", "90", "s", "10w", "-5m", "1.2.3s"] {
+        assert!(parse_span(bad).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn spans_stop_at_a_year() {
+    assert_eq!(parse_span("365d"), Ok(Duration::from_secs(365 * 86400)));
+    assert!(parse_span("366d").is_err());
+    // Too big for a Duration at all, let alone a year
+    assert!(parse_span("99999999999999999999999d").is_err());
+}
+
+#[test]
+fn ranges_share_the_upper_unit() {
+    let secs = |min, max| Ok((Duration::from_secs(min), Duration::from_secs(max)));
+    assert_eq!(parse_span_range("20-90s"), secs(20, 90));
+    assert_eq!(parse_span_range("30s-2m"), secs(30, 120));
+    assert_eq!(parse_span_range("1-2h"), secs(3600, 7200));
+    assert!(parse_span_range("20s").is_err());
+    assert!(parse_span_range("20-90").is_err());
+}
+
+#[test]
+fn mix_layers_take_an_optional_volume() {
+    assert_eq!(parse_mix_layer("rain").unwrap(), ("rain", None));
+    assert_eq!(parse_mix_layer("rain@40").unwrap(), ("rain", Some(0.4)));
+    assert_eq!(parse_mix_layer("cafe@25%").unwrap(), ("cafe", Some(0.25)));
+    assert_eq!(parse_mix_layer("rain@150").unwrap(), ("rain", Some(1.0)));
+    assert_eq!(parse_mix_layer("a@b@10").unwrap(), ("a@b", Some(0.1)));
+    for bad in ["rain@nan", "rain@inf", "rain@-inf", "rain@", "rain@loud"] {
+        assert!(parse_mix_layer(bad).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn levels_set_or_step_the_volume() {
+    assert_eq!(parse_level("30", 0.8).unwrap(), 0.3);
+    assert_eq!(parse_level("+10", 0.5).unwrap(), 0.6);
+    assert_eq!(parse_level("-10", 0.5).unwrap(), 0.4);
+    assert_eq!(parse_level("+10", 0.95).unwrap(), 1.0);
+    assert_eq!(parse_level("-10", 0.05).unwrap(), 0.0);
+    assert_eq!(parse_level("150", 0.5).unwrap(), 1.0);
+    for bad in ["nan", "NaN", "inf", "-inf", "loud", ""] {
+        assert!(parse_level(bad, 0.5).is_err(), "{:?}", bad);
+    }
+}
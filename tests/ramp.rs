@@ -0,0 +1,53 @@
+//! Gain ramps as a layer plays them.
+#![allow(dead_code)]
+
+#[path = "../src/ramp.rs"]
+mod ramp;
+
+use ramp::{Gain, RampedSource};
+use rodio::buffer::SamplesBuffer;
+use std::time::Duration;
+
+/// One second of full-scale DC at 1kHz, so a millisecond is one frame.
+fn dc(channels: u16) -> SamplesBuffer<f32> {
+    SamplesBuffer::new(channels, 1000, vec![1.0; 1000 * channels as usize])
+}
+
+#[test]
+fn reaches_the_target_after_the_ramp_time() {
+    let gain = Gain::new();
+    gain.ramp_to(0.8, Duration::from_millis(20));
+    let out: Vec<f32> = RampedSource::new(dc(1), gain).collect();
+
+    assert!(out.windows(2).take(19).all(|w| w[1] > w[0]));
+    assert!(out[18] < 0.8);
+    assert!(out[19..].iter().all(|&s| (s - 0.8).abs() < 1e-6));
+}
+
+#[test]
+fn ramps_both_channels_of_a_frame_together() {
+    let gain = Gain::new();
+    gain.ramp_to(1.0, Duration::from_millis(10));
+    let out: Vec<f32> = RampedSource::new(dc(2), gain).collect();
+
+    assert!(out.chunks(2).all(|frame| frame[0] == frame[1]));
+    assert_eq!(out[18], 1.0);
+}
+
+#[test]
+fn a_zero_ramp_jumps_straight_to_the_target() {
+    let gain = Gain::new();
+    gain.ramp_to(0.5, Duration::ZERO);
+    let out: Vec<f32> = RampedSource::new(dc(1), gain.clone()).take(10).collect();
+    assert!(out.iter().all(|&s| s == 0.5));
+}
+
+#[test]
+fn stopping_ends_the_source() {
+    let gain = Gain::new();
+    gain.ramp_to(1.0, Duration::ZERO);
+    let mut source = RampedSource::new(dc(1), gain.clone());
+    assert_eq!(source.next(), Some(1.0));
+    gain.stop();
+    assert_eq!(source.next(), None);
+}
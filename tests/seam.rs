@@ -0,0 +1,60 @@
+//! Loop seams crossfaded by `crossfade_loop`.
+#![allow(dead_code)]
+
+mod common;
+
+#[path = "../src/api.rs"]
+mod api;
+#[path = "../src/cache.rs"]
+mod cache;
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/generator.rs"]
+mod generator;
+#[path = "../src/loudness.rs"]
+mod loudness;
+#[path = "../src/seam.rs"]
+mod seam;
+
+use common::{unwav, wav};
+use std::time::Duration;
+
+const RATE: u32 = 8000;
+
+/// Two seconds of a 440Hz sine, which doesn't fit the clip a whole number
+/// of times, so looping it as is would click.
+fn sine() -> Vec<f32> {
+    common::sine(440.0, 0.5, RATE, 2 * RATE as usize)
+}
+
+#[test]
+fn loops_without_a_jump_at_the_seam() {
+    let input = sine();
+    let fade = RATE as usize / 5;
+    let out =
+        unwav(&seam::crossfade_loop(&wav(1, RATE, &input), Duration::from_millis(200)).unwrap());
+
+    // The head's crossfade is cut off, and at most a quarter of the clip
+    // is searched for the loop point
+    assert!(out.len() <= input.len() - fade);
+    assert!(out.len() >= input.len() - fade - input.len() / 4);
+
+    // Wrapping round is no bigger a step than the sine takes anyway
+    let largest_step = out
+        .windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .fold(0.0, f32::max);
+    let seam = (out[0] - out[out.len() - 1]).abs();
+    assert!(
+        seam <= largest_step * 1.05,
+        "seam {} vs {}",
+        seam,
+        largest_step
+    );
+}
+
+#[test]
+fn refuses_clips_too_short_to_crossfade() {
+    let data = wav(1, RATE, &sine()[..100]);
+    assert!(seam::crossfade_loop(&data, Duration::from_millis(200)).is_err());
+}
@@ -0,0 +1,61 @@
+//! Panning and width as `StereoSource` applies them.
+#![allow(dead_code)]
+
+#[path = "../src/stereo.rs"]
+mod stereo;
+
+use rodio::buffer::SamplesBuffer;
+use stereo::{Stereo, StereoControl, StereoSource};
+
+fn place(channels: u16, samples: Vec<f32>, stereo: Stereo) -> Vec<f32> {
+    let input = SamplesBuffer::new(channels, 44100, samples);
+    StereoSource::new(input, StereoControl::new(stereo)).collect()
+}
+
+fn panned(pan: f32) -> Stereo {
+    Stereo { pan, width: 1.0 }
+}
+
+#[test]
+fn mono_plays_at_full_level_in_both_channels_at_centre() {
+    let out = place(1, vec![0.5; 100], Stereo::default());
+    assert_eq!(out.len(), 200);
+    for frame in out.chunks(2) {
+        assert!((frame[0] - 0.5).abs() < 1e-6);
+        assert!((frame[1] - 0.5).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn mono_pans_with_constant_power() {
+    for pan in [-1.0, -0.6, -0.2, 0.0, 0.3, 0.7, 1.0] {
+        let out = place(1, vec![1.0; 4], panned(pan));
+        let power = out[0] * out[0] + out[1] * out[1];
+        assert!((power - 2.0).abs() < 1e-5, "pan {}: power {}", pan, power);
+        if pan != 0.0 {
+            assert_eq!(out[0] > out[1], pan < 0.0, "pan {}", pan);
+        }
+    }
+    let hard_left = place(1, vec![1.0; 4], panned(-1.0));
+    assert!(hard_left[1].abs() < 1e-6);
+}
+
+#[test]
+fn stereo_passes_through_at_the_defaults_and_folds_to_mono_at_zero_width() {
+    let input = vec![0.8, -0.4, 0.2, 0.6];
+    let out = place(2, input.clone(), Stereo::default());
+    for (o, i) in out.iter().zip(&input) {
+        assert!((o - i).abs() < 1e-6);
+    }
+
+    let out = place(
+        2,
+        input,
+        Stereo {
+            pan: 0.0,
+            width: 0.0,
+        },
+    );
+    assert!((out[0] - 0.2).abs() < 1e-6 && (out[1] - 0.2).abs() < 1e-6);
+    assert!((out[2] - 0.4).abs() < 1e-6 && (out[3] - 0.4).abs() < 1e-6);
+}
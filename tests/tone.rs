@@ -0,0 +1,94 @@
+//! The three-band EQ run by `ToneSource`.
+#![allow(dead_code)]
+
+#[path = "../src/tone.rs"]
+mod tone;
+
+use rodio::buffer::SamplesBuffer;
+use tone::{Tone, ToneControl, ToneSource};
+
+const RATE: u32 = 44100;
+
+fn shape(samples: Vec<f32>, tone: Tone) -> Vec<f32> {
+    let input = SamplesBuffer::new(1, RATE, samples);
+    ToneSource::new(input, ToneControl::new(tone)).collect()
+}
+
+/// Level the EQ settles to on a constant input, after a second.
+fn dc_gain(tone: Tone) -> f32 {
+    *shape(vec![1.0; RATE as usize], tone).last().unwrap()
+}
+
+/// Level the EQ settles to at the Nyquist frequency, after a second.
+fn nyquist_gain(tone: Tone) -> f32 {
+    let input = (0..RATE)
+        .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+        .collect();
+    let out = shape(input, tone);
+    out[out.len() - 2..]
+        .iter()
+        .map(|s| s.abs())
+        .fold(0.0, f32::max)
+}
+
+fn db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+#[test]
+fn flat_eq_leaves_samples_untouched() {
+    let input: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.37).sin() * 0.9).collect();
+    let out = shape(input.clone(), Tone::default());
+    assert_eq!(
+        out.iter().map(|s| s.to_bits()).collect::<Vec<_>>(),
+        input.iter().map(|s| s.to_bits()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn low_shelf_sets_the_gain_at_dc_only() {
+    for gain in [-12.0, -6.0, 6.0] {
+        let low = Tone {
+            low: gain,
+            ..Tone::default()
+        };
+        assert!((db(dc_gain(low)) - gain).abs() < 0.05, "low {}dB", gain);
+        assert!(db(nyquist_gain(low)).abs() < 0.05, "low {}dB", gain);
+    }
+}
+
+#[test]
+fn high_shelf_sets_the_gain_at_nyquist_only() {
+    for gain in [-12.0, -6.0, 6.0] {
+        let high = Tone {
+            high: gain,
+            ..Tone::default()
+        };
+        assert!(
+            (db(nyquist_gain(high)) - gain).abs() < 0.05,
+            "high {}dB",
+            gain
+        );
+        assert!(db(dc_gain(high)).abs() < 0.05, "high {}dB", gain);
+    }
+}
+
+#[test]
+fn mid_peak_leaves_both_ends_alone() {
+    let mid = Tone {
+        mid: 9.0,
+        ..Tone::default()
+    };
+    assert!(db(dc_gain(mid)).abs() < 0.05);
+    assert!(db(nyquist_gain(mid)).abs() < 0.05);
+}
+
+#[test]
+fn gains_beyond_the_limit_are_refused() {
+    let loud = Tone {
+        mid: tone::MAX_GAIN_DB + 1.0,
+        ..Tone::default()
+    };
+    assert!(loud.validate().is_err());
+    assert_eq!(loud.clamped().mid, tone::MAX_GAIN_DB);
+}